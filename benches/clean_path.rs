@@ -0,0 +1,64 @@
+//! Compares sanitizing already clean paths against the original `decode` + `PathBuf`
+//! implementation.
+//!
+//! Run with `cargo bench --bench clean_path`.
+
+use std::{
+    path::{Component, PathBuf},
+    str::FromStr,
+};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use http::Uri;
+use tower_sanitize_path::classify_and_sanitize;
+use url_escape::decode;
+
+const PATHS: [&str; 3] = [
+    "/",
+    "/api/v1/users/42/profile",
+    "/static/assets/css/vendor/bootstrap-5.3.0/dist/bootstrap.min.css",
+];
+
+/// The sanitization before parent segments were resolved and clean paths were detected.
+fn baseline(uri: &Uri) -> String {
+    let path = uri.path();
+    let path_decoded = decode(path);
+
+    let trailing_slash = path_decoded.len() > 1 && path_decoded.ends_with('/');
+
+    let path_buf = PathBuf::from_str(&path_decoded).expect("infallible");
+
+    let mut new_path = path_buf
+        .components()
+        .filter(|c| matches!(c, Component::RootDir | Component::Normal(_)))
+        .collect::<PathBuf>()
+        .display()
+        .to_string();
+
+    if trailing_slash {
+        new_path += "/";
+    }
+
+    new_path
+}
+
+fn clean_path(c: &mut Criterion) {
+    let mut group = c.benchmark_group("clean_path");
+
+    for path in PATHS {
+        let uri = Uri::from_static(path);
+        group.throughput(Throughput::Bytes(path.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("baseline", path), &uri, |b, uri| {
+            b.iter(|| baseline(black_box(uri)))
+        });
+        group.bench_with_input(BenchmarkId::new("fast_path", path), &uri, |b, uri| {
+            b.iter(|| classify_and_sanitize(black_box(uri.clone())))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, clean_path);
+criterion_main!(benches);
@@ -0,0 +1,39 @@
+//! Measures how long finding that a long path is already clean takes, to compare the scalar scan
+//! with the `fast-scan` feature's.
+//!
+//! Save the scalar scan as a baseline, then compare the SIMD scan against it:
+//!
+//! ```text
+//! cargo bench --bench clean_scan -- --save-baseline scalar
+//! cargo bench --bench clean_scan --features fast-scan -- --baseline scalar
+//! ```
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use http::Uri;
+use tower_sanitize_path::classify_and_sanitize;
+
+const SEGMENTS: [usize; 4] = [4, 16, 64, 256];
+
+/// A clean path of `segments` segments, like those of deeply nested static assets.
+fn long_path(segments: usize) -> String {
+    (0..segments).map(|i| format!("/assets-{i:03}")).collect()
+}
+
+fn clean_scan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("clean_scan");
+
+    for segments in SEGMENTS {
+        let path = long_path(segments);
+        let uri: Uri = path.parse().unwrap();
+        group.throughput(Throughput::Bytes(path.len() as u64));
+
+        group.bench_with_input(BenchmarkId::from_parameter(path.len()), &uri, |b, uri| {
+            b.iter(|| classify_and_sanitize(black_box(uri.clone())))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, clean_scan);
+criterion_main!(benches);
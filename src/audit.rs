@@ -0,0 +1,291 @@
+//! Auditing of sanitized requests along with the response they got.
+
+use std::{
+    fmt,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use http::{Request, Response, StatusCode, Uri};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    future::{AuditFuture, ResponseFuture},
+    DefaultRejection, RejectResponse, ResponseBody, SanitizeClass, SanitizeConfig, SanitizePath,
+};
+
+/// What happened to a request going through [`SanitizeAudit`], passed to its sink once the
+/// response resolved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AuditRecord {
+    /// The URI as it was received.
+    pub original: Uri,
+    /// The URI the inner service got, the original one unless it was rewritten.
+    pub sanitized: Uri,
+    /// Why the path needed sanitizing, `None` if it was clean.
+    pub class: Option<SanitizeClass>,
+    /// The status of the response, produced by the middleware itself for rejected requests.
+    pub status: StatusCode,
+}
+
+/// Layer that applies [`SanitizeAudit`], which sanitizes paths like [`SanitizePath`] and passes
+/// an [`AuditRecord`] of every request to a sink.
+///
+/// ```
+/// use tower_sanitize_path::{AuditRecord, SanitizeAuditLayer};
+///
+/// let layer = SanitizeAuditLayer::new(|record: AuditRecord| {
+///     if record.class.is_some() {
+///         eprintln!("{} became {}: {}", record.original, record.sanitized, record.status);
+///     }
+/// });
+/// ```
+#[derive(Clone)]
+pub struct SanitizeAuditLayer<F, R = DefaultRejection> {
+    config: Arc<SanitizeConfig>,
+    reject: R,
+    sink: F,
+}
+
+impl<F> SanitizeAuditLayer<F> {
+    /// Create a layer using the default [`SanitizeConfig`], passing records to `sink`.
+    pub fn new(sink: F) -> Self {
+        Self::with_config(SanitizeConfig::new(), sink)
+    }
+
+    /// Create a layer using the given [`SanitizeConfig`], passing records to `sink`.
+    pub fn with_config(config: SanitizeConfig, sink: F) -> Self {
+        Self {
+            config: Arc::new(config),
+            reject: DefaultRejection,
+            sink,
+        }
+    }
+}
+
+impl<F, R> SanitizeAuditLayer<F, R> {
+    /// Build rejection responses with `builder`, like
+    /// [`SanitizePath::reject_response_builder`](crate::SanitizePath::reject_response_builder).
+    pub fn reject_response_builder<R2>(self, builder: R2) -> SanitizeAuditLayer<F, R2> {
+        SanitizeAuditLayer {
+            config: self.config,
+            reject: builder,
+            sink: self.sink,
+        }
+    }
+}
+
+impl<F, R> fmt::Debug for SanitizeAuditLayer<F, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SanitizeAuditLayer")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, F: Clone, R: Clone> Layer<S> for SanitizeAuditLayer<F, R> {
+    type Service = SanitizeAudit<S, F, R>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SanitizeAudit {
+            inner: SanitizePath {
+                inner,
+                config: self.config.clone(),
+                reject: self.reject.clone(),
+            },
+            sink: self.sink.clone(),
+        }
+    }
+}
+
+/// [`SanitizePath`] passing an [`AuditRecord`] of every request to a sink once its response
+/// resolved.
+///
+/// See [`SanitizeAuditLayer`].
+#[derive(Clone)]
+pub struct SanitizeAudit<S, F, R = DefaultRejection> {
+    inner: SanitizePath<S, R>,
+    sink: F,
+}
+
+impl<S: fmt::Debug, F, R> fmt::Debug for SanitizeAudit<S, F, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SanitizeAudit")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, F, R, ReqBody, ResBody> Service<Request<ReqBody>> for SanitizeAudit<S, F, R>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    R: RejectResponse<ReqBody, ResBody>,
+    ResBody: ResponseBody,
+    F: Fn(AuditRecord) + Clone,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = AuditFuture<ResponseFuture<S::Future, ResBody>, F>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let original = req.uri().clone();
+        let mut outcome = Outcome {
+            enabled: true,
+            ..Outcome::default()
+        };
+        let future = self.inner.sanitize(req, &mut outcome);
+
+        AuditFuture::new(future, original, outcome, self.sink.clone())
+    }
+}
+
+/// What [`SanitizePath`] did to a request, kept track of only while it is being audited.
+#[derive(Default)]
+pub(crate) struct Outcome {
+    enabled: bool,
+    class: Option<SanitizeClass>,
+    sanitized: Option<Uri>,
+}
+
+impl Outcome {
+    /// Note that the request needed sanitizing because of `class`.
+    pub(crate) fn note(&mut self, class: SanitizeClass) {
+        self.class = Some(class);
+    }
+
+    /// Note that the URI of the request was rewritten to `uri`.
+    pub(crate) fn rewritten(&mut self, uri: &Uri) {
+        if self.enabled {
+            self.sanitized = Some(uri.clone());
+        }
+    }
+
+    /// The record of the request with the `original` URI, which got a response with `status`.
+    pub(crate) fn record(self, original: Uri, status: StatusCode) -> AuditRecord {
+        AuditRecord {
+            sanitized: self.sanitized.unwrap_or_else(|| original.clone()),
+            original,
+            class: self.class,
+            status,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::Infallible, sync::Mutex};
+
+    use tower::{ServiceBuilder, ServiceExt};
+
+    use super::*;
+
+    async fn audit(config: SanitizeConfig, uri: &str) -> Vec<AuditRecord> {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let records = records.clone();
+            move |record| records.lock().unwrap().push(record)
+        };
+
+        ServiceBuilder::new()
+            .layer(SanitizeAuditLayer::with_config(config, sink))
+            .service_fn(|request: Request<()>| async move {
+                let status = match request.uri().path() {
+                    "/missing" => StatusCode::NOT_FOUND,
+                    _ => StatusCode::OK,
+                };
+                let mut response = Response::new(String::new());
+                *response.status_mut() = status;
+
+                Ok::<_, Infallible>(response)
+            })
+            .oneshot(Request::builder().uri(uri).body(()).unwrap())
+            .await
+            .unwrap();
+
+        let records = records.lock().unwrap().clone();
+        records
+    }
+
+    #[tokio::test]
+    async fn rewritten() {
+        assert_eq!(
+            audit(SanitizeConfig::new(), "/a/../missing?q").await,
+            [AuditRecord {
+                original: "/a/../missing?q".parse().unwrap(),
+                sanitized: "/missing?q".parse().unwrap(),
+                class: Some(SanitizeClass::ParentDir),
+                status: StatusCode::NOT_FOUND,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected() {
+        let config = SanitizeConfig::new().reject_on_traversal(true);
+
+        assert_eq!(
+            audit(config, "/a/../b").await,
+            [AuditRecord {
+                original: "/a/../b".parse().unwrap(),
+                sanitized: "/a/../b".parse().unwrap(),
+                class: Some(SanitizeClass::ParentDir),
+                status: StatusCode::BAD_REQUEST,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_response_builder() {
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let records = records.clone();
+            move |record| records.lock().unwrap().push(record)
+        };
+        let config = SanitizeConfig::new().reject_on_traversal(true);
+        let teapot = |_: &Request<()>, _, _| {
+            let mut response = Response::new(String::new());
+            *response.status_mut() = StatusCode::IM_A_TEAPOT;
+            response
+        };
+
+        let response = ServiceBuilder::new()
+            .layer(SanitizeAuditLayer::with_config(config, sink).reject_response_builder(teapot))
+            .service_fn(|_: Request<()>| async {
+                Ok::<_, Infallible>(Response::new(String::new()))
+            })
+            .oneshot(Request::builder().uri("/a/../b").body(()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::IM_A_TEAPOT);
+        assert_eq!(
+            *records.lock().unwrap(),
+            [AuditRecord {
+                original: "/a/../b".parse().unwrap(),
+                sanitized: "/a/../b".parse().unwrap(),
+                class: Some(SanitizeClass::ParentDir),
+                status: StatusCode::IM_A_TEAPOT,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn clean() {
+        assert_eq!(
+            audit(SanitizeConfig::new(), "/a/b").await,
+            [AuditRecord {
+                original: "/a/b".parse().unwrap(),
+                sanitized: "/a/b".parse().unwrap(),
+                class: None,
+                status: StatusCode::OK,
+            }]
+        );
+    }
+}
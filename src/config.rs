@@ -0,0 +1,1465 @@
+//! Configuration for [`SanitizePath`](crate::SanitizePath).
+
+#[cfg(feature = "percent-decode")]
+use std::borrow::Cow;
+use std::{fmt, path::PathBuf, sync::Arc};
+
+use http::{header, Extensions, HeaderName, HeaderValue, Method, Request, StatusCode, Uri};
+
+use crate::{normalize::decode_unreserved, SanitizePathLayer};
+
+/// Options controlling how [`SanitizePath`](crate::SanitizePath) rewrites paths.
+///
+/// Every declarative option can be set in a `const` context, so a config can be
+/// stored in a `static` without any lazy initialization:
+///
+/// ```
+/// use tower_sanitize_path::{SanitizeConfig, SanitizePathLayer};
+///
+/// static CONFIG: SanitizeConfig = SanitizeConfig::DEFAULT.keep_trailing_slash(false);
+///
+/// let layer = SanitizePathLayer::with_config(CONFIG.clone());
+/// ```
+///
+/// Use [`SanitizeConfig::build`] to check the options are consistent before creating a layer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SanitizeConfig {
+    pub(crate) path_strategy: PathStrategy,
+    pub(crate) keep_trailing_slash: bool,
+    pub(crate) root_behavior: RootBehavior,
+    pub(crate) slash_mode: SlashMode,
+    pub(crate) decode_mode: DecodeMode,
+    #[cfg(feature = "percent-decode")]
+    pub(crate) decoder: Option<Decoder>,
+    pub(crate) invalid_utf8: InvalidUtf8,
+    pub(crate) noncharacters_invalid: bool,
+    pub(crate) decode_rounds: u8,
+    pub(crate) max_decode_ratio: Option<u8>,
+    pub(crate) empty_result: EmptyResult,
+    pub(crate) max_path_len: Option<usize>,
+    pub(crate) max_segments: Option<usize>,
+    pub(crate) max_segment_len: Option<usize>,
+    pub(crate) oversized_segment: OversizedSegment,
+    pub(crate) max_fs_path_len: Option<usize>,
+    pub(crate) document_root: Option<PathBuf>,
+    pub(crate) virtual_root: Option<String>,
+    pub(crate) root_escape: RootEscape,
+    pub(crate) strip_matrix_params: bool,
+    pub(crate) aggressive_dots: bool,
+    pub(crate) backslash_separator: bool,
+    pub(crate) reject_control_whitespace: bool,
+    pub(crate) strip_control_chars: bool,
+    pub(crate) reject_on_encoded_separator: bool,
+    pub(crate) record_decoded_path: bool,
+    pub(crate) record_outcome: bool,
+    pub(crate) preserve_original_in_uri: bool,
+    pub(crate) dry_run: bool,
+    pub(crate) skip_websocket: bool,
+    pub(crate) reject_on_traversal: bool,
+    pub(crate) redirect: bool,
+    pub(crate) redirect_status: Option<StatusCode>,
+    pub(crate) redirect_loop_guard: Option<HeaderName>,
+    pub(crate) expected_hosts: Vec<String>,
+    pub(crate) denied_paths: Vec<String>,
+    pub(crate) deny_case_insensitive: bool,
+    pub(crate) on_reconstruct_error: OnReconstructError,
+    pub(crate) query_control: QueryControlPolicy,
+    pub(crate) query_control_always: bool,
+    pub(crate) response_header: Option<(HeaderName, HeaderValue)>,
+    pub(crate) rewrite_hook: Option<RewriteHook>,
+    pub(crate) path_dependent: Vec<PathDependent>,
+    #[cfg(feature = "unicode")]
+    pub(crate) unicode_nfc: bool,
+    #[cfg(feature = "unicode")]
+    pub(crate) dot_homoglyphs: bool,
+}
+
+impl SanitizeConfig {
+    /// The default config, usable in `const` and `static` items.
+    pub const DEFAULT: Self = Self::new();
+
+    /// Create the default config.
+    pub const fn new() -> Self {
+        Self {
+            path_strategy: PathStrategy::Segments,
+            keep_trailing_slash: true,
+            root_behavior: RootBehavior::PreserveInput,
+            slash_mode: SlashMode::Collapse,
+            decode_mode: DecodeMode::DEFAULT,
+            #[cfg(feature = "percent-decode")]
+            decoder: None,
+            invalid_utf8: InvalidUtf8::Replace,
+            noncharacters_invalid: false,
+            decode_rounds: 1,
+            max_decode_ratio: None,
+            empty_result: EmptyResult::Root,
+            max_path_len: None,
+            max_segments: None,
+            max_segment_len: None,
+            oversized_segment: OversizedSegment::Reject,
+            max_fs_path_len: None,
+            document_root: None,
+            virtual_root: None,
+            root_escape: RootEscape::Clamp,
+            strip_matrix_params: false,
+            aggressive_dots: false,
+            backslash_separator: false,
+            reject_control_whitespace: false,
+            strip_control_chars: false,
+            reject_on_encoded_separator: false,
+            record_decoded_path: false,
+            record_outcome: false,
+            preserve_original_in_uri: false,
+            dry_run: false,
+            skip_websocket: false,
+            reject_on_traversal: false,
+            redirect: false,
+            redirect_status: None,
+            redirect_loop_guard: None,
+            expected_hosts: Vec::new(),
+            denied_paths: Vec::new(),
+            deny_case_insensitive: false,
+            on_reconstruct_error: OnReconstructError::Passthrough,
+            query_control: QueryControlPolicy::Keep,
+            query_control_always: false,
+            response_header: None,
+            rewrite_hook: None,
+            path_dependent: Vec::new(),
+            #[cfg(feature = "unicode")]
+            unicode_nfc: false,
+            #[cfg(feature = "unicode")]
+            dot_homoglyphs: false,
+        }
+    }
+
+    /// The algorithm used to normalize paths.
+    ///
+    /// Defaults to [`PathStrategy::Segments`].
+    pub const fn path_strategy(mut self, strategy: PathStrategy) -> Self {
+        self.path_strategy = strategy;
+        self
+    }
+
+    /// Keep a trailing slash on the sanitized path.
+    ///
+    /// Defaults to `true`. Setting it to `false` restores the pre-0.2 behavior of
+    /// normalizing trailing slashes away.
+    pub const fn keep_trailing_slash(mut self, keep: bool) -> Self {
+        self.keep_trailing_slash = keep;
+        self
+    }
+
+    /// Whether sanitized paths start with a `/`.
+    ///
+    /// Defaults to [`RootBehavior::PreserveInput`].
+    pub const fn root_behavior(mut self, behavior: RootBehavior) -> Self {
+        self.root_behavior = behavior;
+        self
+    }
+
+    /// How runs of slashes are merged.
+    ///
+    /// Defaults to [`SlashMode::Collapse`].
+    pub const fn slash_mode(mut self, mode: SlashMode) -> Self {
+        self.slash_mode = mode;
+        self
+    }
+
+    /// Which percent-encoded bytes to decode.
+    ///
+    /// Defaults to `DecodeMode::Full`, or [`DecodeMode::TraversalOnly`] without the
+    /// `percent-decode` feature.
+    pub const fn decode_mode(mut self, mode: DecodeMode) -> Self {
+        self.decode_mode = mode;
+        self
+    }
+
+    /// Use a custom percent-decoder instead of the default [`url_escape::decode`].
+    ///
+    /// The decoder is used for [`DecodeMode::Full`], so it should decode every percent-encoded
+    /// byte. It is called once per [decode round](Self::decode_rounds), and should return
+    /// [`Cow::Borrowed`] when nothing was decoded.
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    /// use tower_sanitize_path::SanitizeConfig;
+    ///
+    /// // Also decode `+` as a space
+    /// let config = SanitizeConfig::new().decoder(|path| {
+    ///     match url_escape::decode(path) {
+    ///         decoded if decoded.contains('+') => Cow::Owned(decoded.replace('+', " ")),
+    ///         decoded => decoded,
+    ///     }
+    /// });
+    /// ```
+    #[cfg(feature = "percent-decode")]
+    pub fn decoder<F>(mut self, decoder: F) -> Self
+    where
+        F: Fn(&str) -> Cow<'_, str> + Send + Sync + 'static,
+    {
+        self.decoder = Some(Decoder(Arc::new(decoder)));
+        self
+    }
+
+    /// What to do with percent-encoded bytes which do not decode to valid UTF-8, like `%ff`.
+    ///
+    /// Only applies to the default decoder. Defaults to [`InvalidUtf8::Replace`].
+    pub const fn invalid_utf8(mut self, invalid: InvalidUtf8) -> Self {
+        self.invalid_utf8 = invalid;
+        self
+    }
+
+    /// Handle decoded Unicode noncharacters, like `U+FFFE` or `U+FDD0`, as
+    /// [`invalid_utf8`](Self::invalid_utf8) handles invalid sequences.
+    ///
+    /// Surrogates, like the `%ed%a0%80` encoding `U+D800` in WTF-8, are never valid UTF-8 so
+    /// they are always replaced or rejected. Noncharacters are valid but, meant for internal
+    /// use only, can smuggle paths past checks in other systems. Defaults to `false`.
+    pub const fn noncharacters_invalid(mut self, invalid: bool) -> Self {
+        self.noncharacters_invalid = invalid;
+        self
+    }
+
+    /// How many times to percent-decode the path, to catch double encoded paths like
+    /// `/%252e%252e/secret`.
+    ///
+    /// Decoding stops early once a round leaves the path unchanged. Defaults to `1`.
+    pub const fn decode_rounds(mut self, rounds: u8) -> Self {
+        self.decode_rounds = rounds;
+        self
+    }
+
+    /// Respond with `400 Bad Request` when a decode round leaves the path more than `ratio`
+    /// times longer than it was received.
+    ///
+    /// This only matters with a custom [decoder](Self::decoder): percent-decoding never
+    /// lengthens a path, so with the built-in decoder the limit is never reached. It guards
+    /// against custom decoders amplifying small inputs, round after round. Unlimited by default.
+    pub const fn max_decode_ratio(mut self, ratio: u8) -> Self {
+        self.max_decode_ratio = Some(ratio);
+        self
+    }
+
+    /// What to do with paths resolving to nothing, like `/../..`.
+    ///
+    /// Defaults to [`EmptyResult::Root`].
+    pub const fn empty_result(mut self, empty_result: EmptyResult) -> Self {
+        self.empty_result = empty_result;
+        self
+    }
+
+    /// The longest a path may be as received, in bytes, before anything is decoded.
+    ///
+    /// Requests for longer paths get a `414 URI Too Long`. Unlimited by default.
+    pub const fn max_path_len(mut self, len: usize) -> Self {
+        self.max_path_len = Some(len);
+        self
+    }
+
+    /// The most non-empty segments a path may have, both as received and once sanitized.
+    ///
+    /// Requests for paths with more get a `400 Bad Request`. Unlimited by default.
+    pub const fn max_segments(mut self, segments: usize) -> Self {
+        self.max_segments = Some(segments);
+        self
+    }
+
+    /// The longest a single segment may be after decoding, in bytes.
+    ///
+    /// Many filesystems cap file names at 255 bytes, and some backends misbehave on longer ones.
+    /// Longer segments are handled according to
+    /// [`oversized_segment`](Self::oversized_segment). Unlimited by default.
+    pub const fn max_segment_len(mut self, len: usize) -> Self {
+        self.max_segment_len = Some(len);
+        self
+    }
+
+    /// What to do with segments longer than [`max_segment_len`](Self::max_segment_len).
+    ///
+    /// Defaults to [`OversizedSegment::Reject`].
+    pub const fn oversized_segment(mut self, oversized: OversizedSegment) -> Self {
+        self.oversized_segment = oversized;
+        self
+    }
+
+    /// The longest the filesystem path a request maps to may be, in bytes, like the 4096 byte
+    /// `PATH_MAX` of Linux.
+    ///
+    /// The filesystem path is the decoded sanitized path appended to the
+    /// [`document_root`](Self::document_root). Requests for longer paths get a
+    /// `400 Bad Request`, instead of failing to open the file further down. Unlimited by default.
+    pub const fn max_fs_path_len(mut self, len: usize) -> Self {
+        self.max_fs_path_len = Some(len);
+        self
+    }
+
+    /// The directory files are served from, counted towards the
+    /// [`max_fs_path_len`](Self::max_fs_path_len).
+    ///
+    /// Defaults to none, only counting the path itself.
+    pub fn document_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.document_root = Some(root.into());
+        self
+    }
+
+    /// Keep sanitized paths under `root`, like the `/app` an application is mounted at behind a
+    /// reverse proxy.
+    ///
+    /// Paths resolving outside of it, like `/app/../../etc` or `/other`, are handled according to
+    /// [`root_escape`](Self::root_escape). The root must start with a `/`, a trailing one is
+    /// ignored. Unset by default.
+    pub fn virtual_root(mut self, root: impl Into<String>) -> Self {
+        let mut root = root.into();
+        while root.len() > 1 && root.ends_with('/') {
+            root.pop();
+        }
+        self.virtual_root = Some(root);
+        self
+    }
+
+    /// What to do with paths resolving outside of the [`virtual_root`](Self::virtual_root).
+    ///
+    /// Defaults to [`RootEscape::Clamp`].
+    pub const fn root_escape(mut self, escape: RootEscape) -> Self {
+        self.root_escape = escape;
+        self
+    }
+
+    /// Strip matrix parameters, a `;` and everything after it, from every segment.
+    ///
+    /// Servlet containers, among others, ignore these so `/..;/secret` is seen as `/../secret`.
+    /// Stripping them neutralizes the traversal, giving `/secret`. Defaults to `false`.
+    pub const fn strip_matrix_params(mut self, strip: bool) -> Self {
+        self.strip_matrix_params = strip;
+        self
+    }
+
+    /// Treat every segment made only of dots, like `...` or `....`, as `..`, as some backends and
+    /// WAF heuristics do.
+    ///
+    /// Such segments are plain names by the RFC, so `/a/..../secret` is kept as is by default,
+    /// but becomes `/secret` with this enabled. Defaults to `false`.
+    pub const fn aggressive_dots(mut self, aggressive: bool) -> Self {
+        self.aggressive_dots = aggressive;
+        self
+    }
+
+    /// Treat `\` as a path separator, like Windows and some proxies do.
+    ///
+    /// Backslashes are replaced after decoding, so both `/..\secret` and `/%2e%2e%5csecret` become
+    /// `/secret`. Defaults to `false`.
+    pub const fn backslash_separator(mut self, enable: bool) -> Self {
+        self.backslash_separator = enable;
+        self
+    }
+
+    /// Respond with `400 Bad Request` to paths containing control whitespace (tab, newline,
+    /// vertical tab, form feed or carriage return), which some backends treat as the end of the
+    /// path.
+    ///
+    /// Otherwise all whitespace is kept in its segment and percent-encoded in the sanitized path,
+    /// so `/safe%20/../secret` becomes `/secret`. Defaults to `false`.
+    pub const fn reject_control_whitespace(mut self, reject: bool) -> Self {
+        self.reject_control_whitespace = reject;
+        self
+    }
+
+    /// Strip the [control characters](crate::CONTROL_CHARS) from decoded paths before looking for
+    /// dot-segments, as some backends drop them.
+    ///
+    /// `/..%00/secret` then becomes `/secret` instead of keeping the `..%00` segment. Control
+    /// whitespace is still rejected first if
+    /// [`reject_control_whitespace`](Self::reject_control_whitespace) is enabled. Defaults to
+    /// `false`.
+    pub const fn strip_control_chars(mut self, strip: bool) -> Self {
+        self.strip_control_chars = strip;
+        self
+    }
+
+    /// Respond with `400 Bad Request` to paths whose segments change once decoded, because
+    /// encoded separators like `%2f` were smuggled into them.
+    ///
+    /// `/a%2fb` is rejected as it decodes to the two segments of `/a/b`, while `/a%20b` is still
+    /// sanitized as usual. Defaults to `false`.
+    pub const fn reject_on_encoded_separator(mut self, reject: bool) -> Self {
+        self.reject_on_encoded_separator = reject;
+        self
+    }
+
+    /// Insert a [`DecodedPath`](crate::DecodedPath) extension into rewritten requests.
+    ///
+    /// Defaults to `false`.
+    pub const fn record_decoded_path(mut self, record: bool) -> Self {
+        self.record_decoded_path = record;
+        self
+    }
+
+    /// Insert a [`SanitizeOutcome`](crate::SanitizeOutcome) extension into rewritten requests,
+    /// recording every segment removed and how many decode rounds were needed.
+    ///
+    /// Defaults to `false`.
+    pub const fn record_outcome(mut self, record: bool) -> Self {
+        self.record_outcome = record;
+        self
+    }
+
+    /// Leave the URI of requests untouched and insert the sanitized path as a
+    /// [`SanitizedPath`](crate::SanitizedPath) extension instead.
+    ///
+    /// For stacks where later middlewares need the raw URI, and only some services should see
+    /// the sanitized path. As nothing was rewritten, responses to these requests never get the
+    /// [`response_header`](Self::response_header). Defaults to `false`, rewriting the URI in
+    /// place.
+    pub const fn preserve_original_in_uri(mut self, preserve: bool) -> Self {
+        self.preserve_original_in_uri = preserve;
+        self
+    }
+
+    /// Only detect paths needing sanitization, forwarding them unchanged.
+    ///
+    /// The [`SanitizeClass`](crate::SanitizeClass) that would have applied is inserted into the
+    /// request extensions instead. This includes requests which would have been rejected, for
+    /// example by [`deny_paths`](Self::deny_paths) or [`max_path_len`](Self::max_path_len).
+    /// Defaults to `false`.
+    pub const fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Forward WebSocket handshakes with their URI untouched, to avoid breaking the routing of
+    /// their unusual targets.
+    ///
+    /// A request is a handshake if it is a `GET` with a `Sec-WebSocket-Key` header, an `Upgrade`
+    /// header naming `websocket` and a `Connection` header naming `upgrade`. Only the rewrite is
+    /// skipped: handshakes are still rejected like any other request, for example by
+    /// [`deny_paths`](Self::deny_paths) or [`reject_on_traversal`](Self::reject_on_traversal).
+    ///
+    /// These headers are set by the client, so anyone can have their request skip the rewrite.
+    /// Services behind the middleware must not rely on handshake paths being sanitized. Defaults
+    /// to `false`.
+    pub const fn skip_websocket(mut self, skip: bool) -> Self {
+        self.skip_websocket = skip;
+        self
+    }
+
+    /// Respond with `400 Bad Request` instead of forwarding paths containing parent (`..`)
+    /// traversals.
+    ///
+    /// This includes traversals out of the [`virtual_root`](Self::virtual_root), which are
+    /// rejected even with [`RootEscape::Clamp`]. Defaults to `false`.
+    pub const fn reject_on_traversal(mut self, reject: bool) -> Self {
+        self.reject_on_traversal = reject;
+        self
+    }
+
+    /// Redirect the client to the sanitized path instead of forwarding the request.
+    ///
+    /// Defaults to `false`.
+    pub const fn redirect(mut self, redirect: bool) -> Self {
+        self.redirect = redirect;
+        self
+    }
+
+    /// The status used for redirects, which must be a `3xx` status.
+    ///
+    /// Defaults to `308 Permanent Redirect`.
+    pub const fn redirect_status(mut self, status: StatusCode) -> Self {
+        self.redirect_status = Some(status);
+        self
+    }
+
+    /// Mark redirects with the header `name`, and sanitize and forward requests carrying it
+    /// instead of redirecting them again.
+    ///
+    /// Guards against redirect loops when a client or proxy following the redirect sends the
+    /// marker back along with a path dirtied again. Unset by default.
+    ///
+    /// ```
+    /// use http::HeaderName;
+    /// use tower_sanitize_path::SanitizeConfig;
+    ///
+    /// let config = SanitizeConfig::new()
+    ///     .redirect(true)
+    ///     .redirect_loop_guard(HeaderName::from_static("x-sanitize-redirected"));
+    /// ```
+    pub fn redirect_loop_guard(mut self, name: HeaderName) -> Self {
+        self.redirect_loop_guard = Some(name);
+        self
+    }
+
+    /// Hosts this server answers for, so requests with an absolute-form target like
+    /// `GET http://other.example/ HTTP/1.1` naming any other host get a `421 Misdirected Request`.
+    ///
+    /// Hosts are compared without their port and ignoring ASCII case, after the path has been
+    /// sanitized. Requests in origin-form, like `GET / HTTP/1.1`, are not checked. Defaults to no
+    /// hosts, which disables the check.
+    ///
+    /// ```
+    /// use tower_sanitize_path::SanitizeConfig;
+    ///
+    /// let config = SanitizeConfig::new().expected_hosts(["example.com", "www.example.com"]);
+    /// ```
+    pub fn expected_hosts<I>(mut self, hosts: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.expected_hosts = hosts.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Respond with `403 Forbidden` to requests whose sanitized path is one of `paths`, or under
+    /// one of them.
+    ///
+    /// Paths are matched by whole segments, so denying `/admin` denies `/admin/users` but not
+    /// `/administrator`. They are compared with the sanitized path as forwarded, after decoding
+    /// percent-encoded unreserved characters for the comparison only, so `/%61dmin` is denied
+    /// even when the [`decode_mode`](Self::decode_mode) leaves it encoded. Every entry must start
+    /// with a `/`, see [`validate`](Self::validate). Defaults to no paths.
+    ///
+    /// ```
+    /// use tower_sanitize_path::SanitizeConfig;
+    ///
+    /// let config = SanitizeConfig::new()
+    ///     .deny_paths(["/admin", "/.git"])
+    ///     .deny_case_insensitive(true);
+    /// ```
+    pub fn deny_paths<I>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.denied_paths = paths
+            .into_iter()
+            .map(|path| {
+                let mut path = path.into();
+                while path.len() > 1 && path.ends_with('/') {
+                    path.pop();
+                }
+                path
+            })
+            .collect();
+        self
+    }
+
+    /// Match the [denied paths](Self::deny_paths) ignoring ASCII case, so denying `/secret` also
+    /// denies `/SECRET`.
+    ///
+    /// Only the comparison ignores case, allowed paths are forwarded with their case intact.
+    /// Defaults to `false`.
+    pub const fn deny_case_insensitive(mut self, insensitive: bool) -> Self {
+        self.deny_case_insensitive = insensitive;
+        self
+    }
+
+    /// What to do when the sanitized path cannot be put back into a valid URI.
+    ///
+    /// This should never happen, but security critical deployments may prefer failing closed.
+    /// Defaults to [`OnReconstructError::Passthrough`].
+    pub const fn on_reconstruct_error(mut self, on_error: OnReconstructError) -> Self {
+        self.on_reconstruct_error = on_error;
+        self
+    }
+
+    /// What to do with percent-encoded control characters, like `%00` or `%0a`, in the query of
+    /// requests whose path was rewritten.
+    ///
+    /// Defaults to [`QueryControlPolicy::Keep`].
+    pub const fn query_control_policy(mut self, policy: QueryControlPolicy) -> Self {
+        self.query_control = policy;
+        self
+    }
+
+    /// Apply the [`query_control_policy`](Self::query_control_policy) to every request, not only
+    /// those whose path was rewritten.
+    ///
+    /// Defaults to `false`.
+    pub const fn query_control_always(mut self, always: bool) -> Self {
+        self.query_control_always = always;
+        self
+    }
+
+    /// Decide whether each rewrite is applied, given the original and the sanitized path.
+    ///
+    /// Called before any rejection, redirect or rewrite of a request whose path needs
+    /// sanitizing, but never for requests which are already clean or in a
+    /// [dry run](Self::dry_run).
+    ///
+    /// ```
+    /// use http::StatusCode;
+    /// use tower_sanitize_path::{RewriteDecision, SanitizeConfig};
+    ///
+    /// let config = SanitizeConfig::new().rewrite_hook(|original, _sanitized| {
+    ///     if original.starts_with("/legacy/") {
+    ///         RewriteDecision::KeepOriginal
+    ///     } else if original.contains("..") {
+    ///         RewriteDecision::Reject(StatusCode::FORBIDDEN)
+    ///     } else {
+    ///         RewriteDecision::Apply
+    ///     }
+    /// });
+    /// ```
+    pub fn rewrite_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&str, &str) -> RewriteDecision + Send + Sync + 'static,
+    {
+        self.rewrite_hook = Some(RewriteHook(Arc::new(hook)));
+        self
+    }
+
+    /// Declare `T` as a request extension set by a layer depending on the path, like the matched
+    /// route of a router.
+    ///
+    /// A request reaching the middleware with `T` already set means that layer runs first and saw
+    /// the unsanitized path. In debug builds with the `tracing` feature this emits a `WARN` event,
+    /// see [`SanitizePathLayer::around`] for keeping the middleware in front.
+    pub fn path_dependent_extension<T: Send + Sync + 'static>(mut self) -> Self {
+        self.path_dependent.push(PathDependent {
+            name: std::any::type_name::<T>(),
+            is_set: |extensions| extensions.get::<T>().is_some(),
+        });
+        self
+    }
+
+    /// Add a header to the inner service's responses to requests whose path was rewritten, like
+    /// `X-Path-Sanitized: true`, so monitoring or CDN layers can act on them.
+    ///
+    /// Rejections, redirects and requests which were already clean never get the header, nor do
+    /// requests left untouched by [`preserve_original_in_uri`](Self::preserve_original_in_uri).
+    pub fn response_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.response_header = Some((name, value));
+        self
+    }
+
+    /// Normalize decoded paths to Unicode [NFC](https://unicode.org/reports/tr15/), so every
+    /// spelling of a non-ASCII path reaches the service the same way.
+    ///
+    /// The decomposed `/%D0%B8%CC%86` becomes `/%D0%B9`, both spelling `/й`. Defaults to `false`.
+    #[cfg(feature = "unicode")]
+    pub const fn unicode_nfc(mut self, nfc: bool) -> Self {
+        self.unicode_nfc = nfc;
+        self
+    }
+
+    /// Treat segments spelled with homoglyphs of `.`, like the fullwidth `U+FF0E` or the
+    /// ideographic `U+3002` full stop, as the dot-segments some backends read them as.
+    ///
+    /// `/a/%EF%BC%8E%EF%BC%8E/secret` becomes `/secret`. Only whole segments of one or two dots
+    /// are folded, `/file%E3%80%82txt` is left alone. Defaults to `false`.
+    #[cfg(feature = "unicode")]
+    pub const fn dot_homoglyphs(mut self, dot_homoglyphs: bool) -> Self {
+        self.dot_homoglyphs = dot_homoglyphs;
+        self
+    }
+
+    /// Check the options are consistent.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.decode_rounds == 0 {
+            return Err(ConfigError::ZeroDecodeRounds);
+        }
+
+        if self.max_decode_ratio == Some(0) {
+            return Err(ConfigError::ZeroDecodeRatio);
+        }
+
+        if self.max_path_len == Some(0) {
+            return Err(ConfigError::ZeroPathLen);
+        }
+
+        if self.max_segment_len == Some(0) {
+            return Err(ConfigError::ZeroSegmentLen);
+        }
+
+        if let Some(root) = &self.virtual_root {
+            if !root.starts_with('/') {
+                return Err(ConfigError::RelativeVirtualRoot);
+            }
+        }
+
+        if self.root_escape != RootEscape::Clamp && self.virtual_root.is_none() {
+            return Err(ConfigError::RootEscapeWithoutVirtualRoot);
+        }
+
+        if self.oversized_segment != OversizedSegment::Reject && self.max_segment_len.is_none() {
+            return Err(ConfigError::OversizedSegmentWithoutMaxSegmentLen);
+        }
+
+        if self.document_root.is_some() && self.max_fs_path_len.is_none() {
+            return Err(ConfigError::DocumentRootWithoutMaxFsPathLen);
+        }
+
+        if let Some(path) = self.denied_paths.iter().find(|path| !path.starts_with('/')) {
+            return Err(ConfigError::RelativeDenyPath(path.clone()));
+        }
+
+        if self.deny_case_insensitive && self.denied_paths.is_empty() {
+            return Err(ConfigError::DenyCaseInsensitiveWithoutDenyPaths);
+        }
+
+        if self.path_strategy != PathStrategy::Segments && self.sets_segments_options() {
+            return Err(ConfigError::OptionsIgnoredByStrategy(self.path_strategy));
+        }
+
+        if self.dry_run && self.reject_on_traversal {
+            return Err(ConfigError::DryRunWithReject);
+        }
+
+        if self.dry_run && self.redirect {
+            return Err(ConfigError::DryRunWithRedirect);
+        }
+
+        if let Some(status) = self.redirect_status {
+            if !self.redirect {
+                return Err(ConfigError::RedirectStatusWithoutRedirect);
+            }
+
+            if !status.is_redirection() {
+                return Err(ConfigError::InvalidRedirectStatus(status));
+            }
+        }
+
+        if self.redirect_loop_guard.is_some() && !self.redirect {
+            return Err(ConfigError::RedirectLoopGuardWithoutRedirect);
+        }
+
+        if let OnReconstructError::Reject(status) = self.on_reconstruct_error {
+            if !status.is_client_error() && !status.is_server_error() {
+                return Err(ConfigError::InvalidRejectStatus(status));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether any option only used by [`PathStrategy::Segments`] differs from its default.
+    fn sets_segments_options(&self) -> bool {
+        let default = &Self::DEFAULT;
+
+        #[cfg(feature = "percent-decode")]
+        if self.decoder.is_some() {
+            return true;
+        }
+        #[cfg(feature = "unicode")]
+        if self.unicode_nfc || self.dot_homoglyphs {
+            return true;
+        }
+
+        self.keep_trailing_slash != default.keep_trailing_slash
+            || self.root_behavior != default.root_behavior
+            || self.slash_mode != default.slash_mode
+            || self.decode_mode != default.decode_mode
+            || self.invalid_utf8 != default.invalid_utf8
+            || self.noncharacters_invalid
+            || self.decode_rounds != default.decode_rounds
+            || self.max_decode_ratio.is_some()
+            || self.empty_result != default.empty_result
+            || self.max_segment_len.is_some()
+            || self.oversized_segment != default.oversized_segment
+            || self.strip_matrix_params
+            || self.aggressive_dots
+            || self.backslash_separator
+            || self.reject_control_whitespace
+            || self.strip_control_chars
+            || self.reject_on_encoded_separator
+    }
+
+    /// Check the options are consistent and create a [`SanitizePathLayer`] using them.
+    ///
+    /// ```
+    /// use http::StatusCode;
+    /// use tower_sanitize_path::{ConfigError, SanitizeConfig};
+    ///
+    /// let layer = SanitizeConfig::new().reject_on_traversal(true).build();
+    /// assert!(layer.is_ok());
+    ///
+    /// let layer = SanitizeConfig::new()
+    ///     .redirect(true)
+    ///     .redirect_status(StatusCode::OK)
+    ///     .build();
+    /// assert_eq!(layer.unwrap_err(), ConfigError::InvalidRedirectStatus(StatusCode::OK));
+    /// ```
+    pub fn build(self) -> Result<SanitizePathLayer, ConfigError> {
+        self.validate()?;
+
+        Ok(SanitizePathLayer::with_config(self))
+    }
+
+    pub(crate) fn redirect_status_or_default(&self) -> StatusCode {
+        self.redirect_status
+            .unwrap_or(StatusCode::PERMANENT_REDIRECT)
+    }
+
+    /// Whether `request` is a WebSocket handshake to skip, see [`skip_websocket`](Self::skip_websocket).
+    pub(crate) fn skips_websocket<B>(&self, request: &Request<B>) -> bool {
+        if !self.skip_websocket {
+            return false;
+        }
+
+        let headers = request.headers();
+        let names = |name, token: &str| {
+            headers
+                .get_all(name)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .any(|value| value.trim().eq_ignore_ascii_case(token))
+        };
+
+        request.method() == Method::GET
+            && headers.contains_key(header::SEC_WEBSOCKET_KEY)
+            && names(header::UPGRADE, "websocket")
+            && names(header::CONNECTION, "upgrade")
+    }
+
+    /// Whether the sanitized `path` maps to a filesystem path longer than the
+    /// [`max_fs_path_len`](Self::max_fs_path_len).
+    pub(crate) fn exceeds_fs_path_len(&self, path: &str) -> bool {
+        let Some(max) = self.max_fs_path_len else {
+            return false;
+        };
+
+        // Every `%XX` decodes to a single byte
+        let bytes = path.as_bytes();
+        let encoded = (0..bytes.len())
+            .filter(|&i| {
+                bytes[i] == b'%'
+                    && matches!(
+                        bytes.get(i + 1..i + 3),
+                        Some(hex) if hex.iter().all(u8::is_ascii_hexdigit)
+                    )
+            })
+            .count();
+        let root = self.document_root.as_ref().map_or(0, |root| {
+            // Joined without doubling the separator
+            let doubled = root.to_string_lossy().ends_with('/') && path.starts_with('/');
+            root.as_os_str().len() - usize::from(doubled)
+        });
+
+        root + path.len() - 2 * encoded > max
+    }
+
+    /// Whether the sanitized `path` is one of the [denied paths](Self::deny_paths) or under one.
+    pub(crate) fn denies(&self, path: &str) -> bool {
+        if self.denied_paths.is_empty() {
+            return false;
+        }
+
+        // Backends decoding the path would serve `/%61dmin` as `/admin`
+        let path = &*decode_unreserved(path);
+        self.denied_paths.iter().any(|denied| {
+            let Some(prefix) = path.get(..denied.len()) else {
+                return false;
+            };
+            let matches = if self.deny_case_insensitive {
+                prefix.eq_ignore_ascii_case(denied)
+            } else {
+                prefix == denied
+            };
+
+            matches
+                && (denied == "/" || matches!(path.as_bytes().get(denied.len()), None | Some(b'/')))
+        })
+    }
+
+    /// Whether the authority of `uri`, if any, names one of the [expected
+    /// hosts](Self::expected_hosts).
+    pub(crate) fn serves_authority(&self, uri: &Uri) -> bool {
+        match uri.host() {
+            Some(host) if !self.expected_hosts.is_empty() => self
+                .expected_hosts
+                .iter()
+                .any(|expected| expected.eq_ignore_ascii_case(host)),
+            _ => true,
+        }
+    }
+}
+
+impl Default for SanitizeConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What [`SanitizePath`](crate::SanitizePath) does when a sanitized path cannot be put back into
+/// a valid URI.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OnReconstructError {
+    /// Forward the request with its original, unsanitized, URI.
+    #[default]
+    Passthrough,
+    /// Respond with the given `4xx` or `5xx` status.
+    Reject(StatusCode),
+}
+
+/// What to do with a rewrite, decided by a [`SanitizeConfig::rewrite_hook`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RewriteDecision {
+    /// Go ahead with the rewrite, or the configured rejection or redirect.
+    Apply,
+    /// Respond with the given status instead.
+    Reject(StatusCode),
+    /// Forward the request with its original path, untouched.
+    KeepOriginal,
+}
+
+/// A hook deciding on rewrites, set with [`SanitizeConfig::rewrite_hook`].
+///
+/// Configs are only equal when they share the same hook.
+#[derive(Clone)]
+pub(crate) struct RewriteHook(Arc<RewriteFn>);
+
+type RewriteFn = dyn Fn(&str, &str) -> RewriteDecision + Send + Sync;
+
+impl RewriteHook {
+    pub(crate) fn decide(&self, original: &str, sanitized: &str) -> RewriteDecision {
+        (self.0)(original, sanitized)
+    }
+}
+
+impl fmt::Debug for RewriteHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RewriteHook")
+    }
+}
+
+impl PartialEq for RewriteHook {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for RewriteHook {}
+
+/// An extension declared with [`SanitizeConfig::path_dependent_extension`].
+#[derive(Clone, Debug)]
+pub(crate) struct PathDependent {
+    pub(crate) name: &'static str,
+    // Only checked by debug builds with `tracing`
+    #[cfg_attr(not(all(debug_assertions, feature = "tracing")), allow(dead_code))]
+    pub(crate) is_set: fn(&Extensions) -> bool,
+}
+
+impl PartialEq for PathDependent {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for PathDependent {}
+
+/// A custom percent-decoder set with [`SanitizeConfig::decoder`].
+///
+/// Configs are only equal when they share the same decoder.
+#[cfg(feature = "percent-decode")]
+#[derive(Clone)]
+pub(crate) struct Decoder(Arc<DecodeFn>);
+
+#[cfg(feature = "percent-decode")]
+type DecodeFn = dyn Fn(&str) -> Cow<'_, str> + Send + Sync;
+
+#[cfg(feature = "percent-decode")]
+impl Decoder {
+    pub(crate) fn decode<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        (self.0)(path)
+    }
+}
+
+#[cfg(feature = "percent-decode")]
+impl fmt::Debug for Decoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Decoder")
+    }
+}
+
+#[cfg(feature = "percent-decode")]
+impl PartialEq for Decoder {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(feature = "percent-decode")]
+impl Eq for Decoder {}
+
+/// The algorithm [`SanitizePath`](crate::SanitizePath) normalizes paths with.
+///
+/// Whatever the strategy, paths are still limited by
+/// [`max_path_len`](SanitizeConfig::max_path_len) and
+/// [`max_segments`](SanitizeConfig::max_segments) before normalizing, and kept under the
+/// [`virtual_root`](SanitizeConfig::virtual_root) after.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PathStrategy {
+    /// Decode the path, then remove empty and dot-segments, resolving `..` against the segment
+    /// before it. Configured by every other path option.
+    #[default]
+    Segments,
+    /// Normalize like browsers do, following the path state of the
+    /// [WHATWG URL standard](https://url.spec.whatwg.org/#path-state) for `http` URLs.
+    ///
+    /// Nothing is decoded except encoded dots in dot-segments, like `%2e%2e`, empty segments are
+    /// kept and `\` is a separator. A trailing dot-segment leaves a trailing slash, so `/a/b/..`
+    /// becomes `/a/`. The options only configuring [`Segments`](Self::Segments) are ignored.
+    Whatwg,
+    /// Normalize like Go's [`path.Clean`](https://pkg.go.dev/path#Clean), for services
+    /// migrating from Go.
+    ///
+    /// Nothing is decoded, runs of `/` are merged and dot-segments are resolved, dropping `..` at
+    /// the root but keeping the leading `..` of relative paths. Trailing slashes are removed
+    /// except from `/`, so `/a/b/` becomes `/a/b`. Unlike Go, empty paths are left alone instead
+    /// of becoming `.`. Like [`Whatwg`](Self::Whatwg), it ignores the options of
+    /// [`Segments`](Self::Segments).
+    GoClean,
+}
+
+/// Whether [`SanitizePath`](crate::SanitizePath) gives sanitized paths a leading `/`.
+///
+/// Relative paths only reach services routing relative targets, as request paths normally
+/// always start with a `/`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RootBehavior {
+    /// Keep the leading `/` if the path had one, so `a/../b` becomes `b`.
+    #[default]
+    PreserveInput,
+    /// Always start the path with a `/`, so `a/../b` becomes `/b`.
+    ///
+    /// The empty path and the `*` of `OPTIONS *` requests are left alone.
+    ForceRoot,
+    /// Never start the path with a `/`, so `/a/../b` becomes `b` and `/` becomes empty.
+    Strip,
+}
+
+/// How [`SanitizePath`](crate::SanitizePath) merges runs of slashes in a path.
+///
+/// The query is never touched, so `/a//b?c=//` becomes `/a/b?c=//` in every mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SlashMode {
+    /// Remove every empty segment, so `/a//b/` becomes `/a/b/`, or `/a/b` when
+    /// [`keep_trailing_slash`](SanitizeConfig::keep_trailing_slash) is off.
+    #[default]
+    Collapse,
+    /// Match nginx's [`merge_slashes on`](https://nginx.org/en/docs/http/ngx_http_core_module.html#merge_slashes).
+    ///
+    /// Every run of slashes, including a leading `//`, becomes a single slash. A trailing run is
+    /// merged into a single trailing slash rather than removed, so
+    /// [`keep_trailing_slash`](SanitizeConfig::keep_trailing_slash) is ignored.
+    NginxMerge,
+}
+
+/// What [`SanitizePath`](crate::SanitizePath) does with paths resolving to nothing, like `/../..`
+/// or `/a/..`.
+///
+/// The root path `/` itself is always left alone.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EmptyResult {
+    /// Rewrite the path to `/`.
+    #[default]
+    Root,
+    /// Respond with `400 Bad Request`.
+    Reject,
+    /// Rewrite the path to be empty, for frameworks expecting one.
+    ///
+    /// [`Uri::path`](http::Uri::path) then returns `""`, unless the request has a query in which
+    /// case [`http`] always reports the path as `/`.
+    Empty,
+}
+
+/// What [`SanitizePath`](crate::SanitizePath) does with percent-encoded bytes which are not
+/// valid UTF-8.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InvalidUtf8 {
+    /// Replace invalid sequences with `U+FFFD`, so `/%ff` becomes `/%EF%BF%BD`, and the encoded
+    /// surrogate `/%ed%a0%80` becomes three of them.
+    #[default]
+    Replace,
+    /// Respond with `400 Bad Request`.
+    Reject,
+}
+
+/// What [`SanitizePath`](crate::SanitizePath) does with segments longer than
+/// [`max_segment_len`](SanitizeConfig::max_segment_len).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OversizedSegment {
+    /// Respond with `400 Bad Request`.
+    #[default]
+    Reject,
+    /// Cut the segment down to the limit, without splitting a UTF-8 character.
+    Truncate,
+}
+
+/// What [`SanitizePath`](crate::SanitizePath) does with paths resolving outside of the
+/// [`virtual_root`](SanitizeConfig::virtual_root).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RootEscape {
+    /// Replace the path with the virtual root itself.
+    #[default]
+    Clamp,
+    /// Respond with `400 Bad Request`.
+    Reject,
+}
+
+/// What [`SanitizePath`](crate::SanitizePath) does with percent-encoded control characters in
+/// the query, `%00` to `%1f` and `%7f`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QueryControlPolicy {
+    /// Leave the query untouched.
+    #[default]
+    Keep,
+    /// Remove the encoded control characters, so `?a=b%00c` becomes `?a=bc`.
+    Strip,
+    /// Respond with `400 Bad Request`.
+    Reject,
+}
+
+/// Which percent-encoded bytes [`SanitizePath`](crate::SanitizePath) decodes.
+///
+/// The default depends on the `percent-decode` feature: [`Full`](Self::Full) with it,
+/// [`TraversalOnly`](Self::TraversalOnly) without it. Cargo enables a feature for every crate in
+/// the dependency graph if any of them does, so a crate relying on the `TraversalOnly` default can
+/// be switched to `Full` by another one. Set the mode with
+/// [`SanitizeConfig::decode_mode`](crate::SanitizeConfig::decode_mode) if it matters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DecodeMode {
+    /// Decode every percent-encoded byte, so `/%41` becomes `/A`.
+    ///
+    /// Requires the `percent-decode` feature.
+    #[cfg(feature = "percent-decode")]
+    #[default]
+    Full,
+    /// Only decode the bytes relevant to traversals, `%2e` (`.`) and `%2f` (`/`), leaving every
+    /// other encoded byte untouched.
+    ///
+    /// `/%41/%2e%2e/x` becomes `/%41/x`. The default without the `percent-decode` feature.
+    #[cfg_attr(not(feature = "percent-decode"), default)]
+    TraversalOnly,
+    /// Decode nothing, only resolving literal dot-segments.
+    ///
+    /// For services behind a proxy which already decoded the path, where decoding again would
+    /// reach paths the proxy never checked. `/%2e%2e/x` stays as it is.
+    Literal,
+}
+
+impl DecodeMode {
+    #[cfg(feature = "percent-decode")]
+    const DEFAULT: Self = Self::Full;
+    #[cfg(not(feature = "percent-decode"))]
+    const DEFAULT: Self = Self::TraversalOnly;
+
+    /// Whether every percent-encoded byte is decoded.
+    pub(crate) fn decodes_all(self) -> bool {
+        #[cfg(feature = "percent-decode")]
+        return self == Self::Full;
+        #[cfg(not(feature = "percent-decode"))]
+        return false;
+    }
+}
+
+/// An invalid combination of options in a [`SanitizeConfig`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// `decode_rounds` is zero, so encoded traversals would never be seen.
+    ZeroDecodeRounds,
+    /// `max_decode_ratio` is zero, so every path would be rejected.
+    ZeroDecodeRatio,
+    /// `max_path_len` is zero, so every path would be rejected.
+    ZeroPathLen,
+    /// `max_segment_len` is zero, so no path could have any segment.
+    ZeroSegmentLen,
+    /// `virtual_root` does not start with a `/`, so no path could be under it.
+    RelativeVirtualRoot,
+    /// `dry_run` never changes requests, so it cannot reject them.
+    DryRunWithReject,
+    /// `dry_run` never changes requests, so it cannot redirect them.
+    DryRunWithRedirect,
+    /// A redirect status was set without enabling redirects.
+    RedirectStatusWithoutRedirect,
+    /// The redirect status is not a `3xx` status.
+    InvalidRedirectStatus(StatusCode),
+    /// A rejection status is not a `4xx` or `5xx` status.
+    InvalidRejectStatus(StatusCode),
+    /// `redirect_loop_guard` was set without enabling redirects.
+    RedirectLoopGuardWithoutRedirect,
+    /// `root_escape` was set without a `virtual_root` to escape.
+    RootEscapeWithoutVirtualRoot,
+    /// `oversized_segment` was set without a `max_segment_len`.
+    OversizedSegmentWithoutMaxSegmentLen,
+    /// `document_root` was set without a `max_fs_path_len` to check it against.
+    DocumentRootWithoutMaxFsPathLen,
+    /// An entry of `deny_paths` is empty or does not start with a `/`. An empty entry would deny
+    /// every path, any other would deny none.
+    RelativeDenyPath(String),
+    /// `deny_case_insensitive` was set without any `deny_paths`.
+    DenyCaseInsensitiveWithoutDenyPaths,
+    /// Options only [`PathStrategy::Segments`] uses were set along with another strategy.
+    OptionsIgnoredByStrategy(PathStrategy),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroDecodeRounds => f.write_str("decode rounds must be at least 1"),
+            Self::ZeroDecodeRatio => f.write_str("max decode ratio must be at least 1"),
+            Self::ZeroPathLen => f.write_str("max path length must be at least 1"),
+            Self::ZeroSegmentLen => f.write_str("max segment length must be at least 1"),
+            Self::RelativeVirtualRoot => f.write_str("virtual root must start with a slash"),
+            Self::DryRunWithReject => f.write_str("dry run cannot be combined with rejecting"),
+            Self::DryRunWithRedirect => f.write_str("dry run cannot be combined with redirecting"),
+            Self::RedirectStatusWithoutRedirect => {
+                f.write_str("redirect status is set but redirects are not enabled")
+            }
+            Self::InvalidRedirectStatus(status) => {
+                write!(f, "redirect status {status} is not a 3xx status")
+            }
+            Self::InvalidRejectStatus(status) => {
+                write!(f, "rejection status {status} is not a 4xx or 5xx status")
+            }
+            Self::RedirectLoopGuardWithoutRedirect => {
+                f.write_str("redirect loop guard is set but redirects are not enabled")
+            }
+            Self::RootEscapeWithoutVirtualRoot => {
+                f.write_str("root escape is set but there is no virtual root")
+            }
+            Self::OversizedSegmentWithoutMaxSegmentLen => {
+                f.write_str("oversized segment handling is set but there is no max segment length")
+            }
+            Self::DocumentRootWithoutMaxFsPathLen => {
+                f.write_str("document root is set but there is no max filesystem path length")
+            }
+            Self::RelativeDenyPath(path) => write!(f, "deny path {path:?} must start with a slash"),
+            Self::DenyCaseInsensitiveWithoutDenyPaths => {
+                f.write_str("case insensitive denying is set but no paths are denied")
+            }
+            Self::OptionsIgnoredByStrategy(strategy) => {
+                write!(
+                    f,
+                    "path strategy {strategy:?} ignores the segment options set"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static CONFIG: SanitizeConfig = SanitizeConfig::DEFAULT.keep_trailing_slash(false);
+
+    #[test]
+    fn default_is_const() {
+        const CONFIG: SanitizeConfig = SanitizeConfig::DEFAULT;
+
+        assert_eq!(CONFIG, SanitizeConfig::default());
+    }
+
+    #[test]
+    fn static_config() {
+        let mut uri = "/path/".parse().unwrap();
+        crate::sanitize_path(&CONFIG, &mut uri);
+
+        assert_eq!(uri, "/path");
+    }
+
+    #[test]
+    fn build() {
+        let layer = SanitizeConfig::new()
+            .reject_on_traversal(true)
+            .redirect(true)
+            .redirect_status(StatusCode::MOVED_PERMANENTLY)
+            .decode_rounds(2)
+            .build();
+
+        assert!(layer.is_ok());
+    }
+
+    #[test]
+    fn build_zero_decode_rounds() {
+        let err = SanitizeConfig::new().decode_rounds(0).build().unwrap_err();
+
+        assert_eq!(err, ConfigError::ZeroDecodeRounds);
+    }
+
+    #[test]
+    fn build_zero_decode_ratio() {
+        let err = SanitizeConfig::new()
+            .max_decode_ratio(0)
+            .build()
+            .unwrap_err();
+
+        assert_eq!(err, ConfigError::ZeroDecodeRatio);
+    }
+
+    #[test]
+    fn build_zero_path_len() {
+        let err = SanitizeConfig::new().max_path_len(0).build().unwrap_err();
+
+        assert_eq!(err, ConfigError::ZeroPathLen);
+    }
+
+    #[test]
+    fn build_zero_segment_len() {
+        let err = SanitizeConfig::new()
+            .max_segment_len(0)
+            .build()
+            .unwrap_err();
+
+        assert_eq!(err, ConfigError::ZeroSegmentLen);
+    }
+
+    #[test]
+    fn build_relative_virtual_root() {
+        let err = SanitizeConfig::new()
+            .virtual_root("app")
+            .build()
+            .unwrap_err();
+
+        assert_eq!(err, ConfigError::RelativeVirtualRoot);
+        assert!(SanitizeConfig::new().virtual_root("/app/").build().is_ok());
+    }
+
+    #[test]
+    fn build_relative_deny_path() {
+        for path in ["", "admin"] {
+            let err = SanitizeConfig::new()
+                .deny_paths([path])
+                .build()
+                .unwrap_err();
+
+            assert_eq!(err, ConfigError::RelativeDenyPath(path.to_owned()));
+        }
+        assert!(SanitizeConfig::new()
+            .deny_paths(["/", "/admin/"])
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn build_dry_run_with_reject() {
+        let err = SanitizeConfig::new()
+            .dry_run(true)
+            .reject_on_traversal(true)
+            .build()
+            .unwrap_err();
+
+        assert_eq!(err, ConfigError::DryRunWithReject);
+    }
+
+    #[test]
+    fn build_dry_run_with_redirect() {
+        let err = SanitizeConfig::new()
+            .dry_run(true)
+            .redirect(true)
+            .build()
+            .unwrap_err();
+
+        assert_eq!(err, ConfigError::DryRunWithRedirect);
+    }
+
+    #[test]
+    fn build_redirect_status_without_redirect() {
+        let err = SanitizeConfig::new()
+            .redirect_status(StatusCode::FOUND)
+            .build()
+            .unwrap_err();
+
+        assert_eq!(err, ConfigError::RedirectStatusWithoutRedirect);
+    }
+
+    #[test]
+    fn build_invalid_redirect_status() {
+        let err = SanitizeConfig::new()
+            .redirect(true)
+            .redirect_status(StatusCode::NOT_FOUND)
+            .build()
+            .unwrap_err();
+
+        assert_eq!(
+            err,
+            ConfigError::InvalidRedirectStatus(StatusCode::NOT_FOUND)
+        );
+    }
+
+    #[test]
+    fn build_invalid_reject_status() {
+        let err = SanitizeConfig::new()
+            .on_reconstruct_error(OnReconstructError::Reject(StatusCode::OK))
+            .build()
+            .unwrap_err();
+
+        assert_eq!(err, ConfigError::InvalidRejectStatus(StatusCode::OK));
+    }
+
+    #[test]
+    fn build_unused_options() {
+        let unused = [
+            (
+                SanitizeConfig::new().redirect_loop_guard(HeaderName::from_static("x-sanitized")),
+                ConfigError::RedirectLoopGuardWithoutRedirect,
+            ),
+            (
+                SanitizeConfig::new().root_escape(RootEscape::Reject),
+                ConfigError::RootEscapeWithoutVirtualRoot,
+            ),
+            (
+                SanitizeConfig::new().oversized_segment(OversizedSegment::Truncate),
+                ConfigError::OversizedSegmentWithoutMaxSegmentLen,
+            ),
+            (
+                SanitizeConfig::new().document_root("/srv/www"),
+                ConfigError::DocumentRootWithoutMaxFsPathLen,
+            ),
+            (
+                SanitizeConfig::new().deny_case_insensitive(true),
+                ConfigError::DenyCaseInsensitiveWithoutDenyPaths,
+            ),
+            (
+                SanitizeConfig::new()
+                    .path_strategy(PathStrategy::Whatwg)
+                    .backslash_separator(true),
+                ConfigError::OptionsIgnoredByStrategy(PathStrategy::Whatwg),
+            ),
+            (
+                SanitizeConfig::new()
+                    .path_strategy(PathStrategy::GoClean)
+                    .keep_trailing_slash(false),
+                ConfigError::OptionsIgnoredByStrategy(PathStrategy::GoClean),
+            ),
+        ];
+
+        for (config, err) in unused {
+            assert_eq!(config.build().unwrap_err(), err);
+        }
+
+        let used = SanitizeConfig::new()
+            .redirect(true)
+            .redirect_loop_guard(HeaderName::from_static("x-sanitized"))
+            .virtual_root("/app")
+            .root_escape(RootEscape::Reject)
+            .max_segment_len(255)
+            .oversized_segment(OversizedSegment::Truncate)
+            .max_fs_path_len(4096)
+            .document_root("/srv/www")
+            .deny_paths(["/admin"])
+            .deny_case_insensitive(true);
+        assert!(used.build().is_ok());
+
+        let whatwg = SanitizeConfig::new()
+            .path_strategy(PathStrategy::Whatwg)
+            .virtual_root("/app")
+            .max_path_len(1024);
+        assert!(whatwg.build().is_ok());
+    }
+}
@@ -0,0 +1,35 @@
+//! Errors produced while sanitizing.
+
+use std::{error::Error, fmt};
+
+use http::uri::{InvalidUri, InvalidUriParts};
+
+/// The sanitized path could not be put back into a valid URI.
+///
+/// Sanitized paths are always percent-encoded as needed, so this indicates a bug.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SanitizeError {
+    /// The sanitized path and query are not valid.
+    InvalidPath(InvalidUri),
+    /// The URI could not be rebuilt from its parts.
+    InvalidUri(InvalidUriParts),
+}
+
+impl fmt::Display for SanitizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPath(_) => f.write_str("sanitized path is not valid"),
+            Self::InvalidUri(_) => f.write_str("sanitized uri could not be rebuilt"),
+        }
+    }
+}
+
+impl Error for SanitizeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InvalidPath(err) => Some(err),
+            Self::InvalidUri(err) => Some(err),
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Request extensions inserted by [`SanitizePath`](crate::SanitizePath).
+
+use std::sync::Arc;
+
+use crate::{SanitizeClass, SanitizeConfig, SegmentChange};
+
+/// The path of a request before it was sanitized, exactly as it was received.
+///
+/// Inserted into the request extensions whenever the path is rewritten.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OriginalPath(pub String);
+
+/// The percent-decoded path of a request before any segments were removed, showing what the
+/// client intended to reach.
+///
+/// Inserted into the request extensions alongside [`OriginalPath`] when
+/// [`SanitizeConfig::record_decoded_path`](crate::SanitizeConfig::record_decoded_path) is enabled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedPath(pub String);
+
+/// Everything sanitizing a request's path changed, for downstream logging or policy.
+///
+/// Inserted into the request extensions alongside [`OriginalPath`] or [`SanitizedPath`] when
+/// [`SanitizeConfig::record_outcome`](crate::SanitizeConfig::record_outcome) is enabled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SanitizeOutcome {
+    /// Why the path changed.
+    pub class: SanitizeClass,
+    /// The path exactly as it was received.
+    pub original: String,
+    /// Every segment removed or modified, in the order they were seen.
+    pub removed: Vec<SegmentChange>,
+    /// How many decode rounds changed the path.
+    pub decode_rounds: u8,
+}
+
+/// The sanitized path of a request whose URI was left untouched.
+///
+/// Inserted into the request extensions instead of rewriting the URI when
+/// [`SanitizeConfig::preserve_original_in_uri`](crate::SanitizeConfig::preserve_original_in_uri)
+/// is enabled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SanitizedPath(pub String);
+
+/// Insert into a request's extensions to have [`SanitizePath`](crate::SanitizePath) forward it
+/// untouched.
+///
+/// Useful when the layer is global but some routes, like a static file server doing its own safe
+/// path handling, need the raw path. An earlier layer has to insert it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SkipSanitize;
+
+/// Marks a request as already rewritten with the given config to the given path, so nested
+/// middlewares with the same config skip it as long as nothing changed the path since.
+#[derive(Clone, Debug)]
+pub(crate) struct SanitizedWith(pub(crate) Arc<SanitizeConfig>, pub(crate) String);
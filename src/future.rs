@@ -0,0 +1,156 @@
+//! Response futures for [`SanitizePath`](crate::SanitizePath) and
+//! [`SanitizeAudit`](crate::SanitizeAudit).
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use http::{HeaderName, HeaderValue, Response, Uri};
+use pin_project_lite::pin_project;
+
+use crate::{audit::Outcome, AuditRecord};
+
+pin_project! {
+    /// Response future for [`SanitizePath`](crate::SanitizePath).
+    ///
+    /// Either polls the inner service's future or resolves immediately to a response produced by
+    /// the middleware itself, such as a rejection or redirect, in which case the inner service is
+    /// never called. Responses to rewritten requests
+    /// get the configured [`response_header`](crate::SanitizeConfig::response_header).
+    ///
+    /// Sanitizing happens entirely in `call`, so layers like `timeout` or `load_shed` wrapped
+    /// around the middleware only ever time the inner service's work. Requests passed through to
+    /// the inner service without a response header poll its future directly, with nothing added,
+    /// and [`into_inner`](Self::into_inner) gives back that future itself.
+    pub struct ResponseFuture<F, B> {
+        #[pin]
+        kind: Kind<F, B>,
+    }
+}
+
+pin_project! {
+    #[project = KindProj]
+    enum Kind<F, B> {
+        Inner {
+            #[pin]
+            future: F,
+        },
+        Annotate {
+            #[pin]
+            future: F,
+            header: Option<(HeaderName, HeaderValue)>,
+        },
+        Respond {
+            response: Option<Response<B>>,
+        },
+    }
+}
+
+impl<F, B> ResponseFuture<F, B> {
+    pub(crate) fn inner(future: F) -> Self {
+        Self {
+            kind: Kind::Inner { future },
+        }
+    }
+
+    pub(crate) fn annotate(future: F, header: (HeaderName, HeaderValue)) -> Self {
+        Self {
+            kind: Kind::Annotate {
+                future,
+                header: Some(header),
+            },
+        }
+    }
+
+    pub(crate) fn respond(response: Response<B>) -> Self {
+        Self {
+            kind: Kind::Respond {
+                response: Some(response),
+            },
+        }
+    }
+
+    /// The inner service's future, if the request was passed through to it without the
+    /// middleware changing the response.
+    ///
+    /// Gives the future back unchanged when it resolves to a rejection or redirect, or adds a
+    /// [`response_header`](crate::SanitizeConfig::response_header).
+    pub fn into_inner(self) -> Result<F, Self> {
+        match self.kind {
+            Kind::Inner { future } => Ok(future),
+            kind => Err(Self { kind }),
+        }
+    }
+}
+
+impl<F, B, E> Future for ResponseFuture<F, B>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+{
+    type Output = Result<Response<B>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project().kind.project() {
+            KindProj::Inner { future } => future.poll(cx),
+            KindProj::Annotate { future, header } => {
+                let mut response = ready!(future.poll(cx))?;
+                let (name, value) = header.take().expect("future polled after completion");
+                response.headers_mut().insert(name, value);
+
+                Poll::Ready(Ok(response))
+            }
+            KindProj::Respond { response } => {
+                Poll::Ready(Ok(response.take().expect("future polled after completion")))
+            }
+        }
+    }
+}
+
+pin_project! {
+    /// Response future for [`SanitizeAudit`](crate::SanitizeAudit).
+    ///
+    /// Passes the [`AuditRecord`] of the request to the sink once the response resolved. Nothing
+    /// is recorded if the inner service fails.
+    pub struct AuditFuture<F, S> {
+        #[pin]
+        future: F,
+        original: Option<Uri>,
+        outcome: Option<Outcome>,
+        sink: S,
+    }
+}
+
+impl<F, S> AuditFuture<F, S> {
+    pub(crate) fn new(future: F, original: Uri, outcome: Outcome, sink: S) -> Self {
+        Self {
+            future,
+            original: Some(original),
+            outcome: Some(outcome),
+            sink,
+        }
+    }
+}
+
+impl<F, S, B, E> Future for AuditFuture<F, S>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+    S: Fn(AuditRecord),
+{
+    type Output = Result<Response<B>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let response = ready!(this.future.poll(cx))?;
+
+        let (original, outcome) = this
+            .original
+            .take()
+            .zip(this.outcome.take())
+            .expect("future polled after completion");
+        (this.sink)(outcome.record(original, response.status()));
+
+        Poll::Ready(Ok(response))
+    }
+}
@@ -2,35 +2,133 @@
 
 use std::{
     borrow::Cow,
-    path::{Component, PathBuf},
-    str::FromStr,
+    fmt,
+    sync::Arc,
     task::{Context, Poll},
 };
 
-use http::{Request, Response, Uri};
-use tower_layer::Layer;
+use http::{header, HeaderValue, Request, Response, StatusCode, Uri};
+use tower_layer::{Layer, Stack};
 use tower_service::Service;
-use url_escape::decode;
+
+mod audit;
+mod config;
+mod error;
+mod extension;
+pub mod future;
+mod normalize;
+mod report;
+mod response;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+mod trace;
+
+pub use audit::{AuditRecord, SanitizeAudit, SanitizeAuditLayer};
+pub use config::{
+    ConfigError, DecodeMode, EmptyResult, InvalidUtf8, OnReconstructError, OversizedSegment,
+    PathStrategy, QueryControlPolicy, RewriteDecision, RootBehavior, RootEscape, SanitizeConfig,
+    SlashMode,
+};
+pub use error::SanitizeError;
+pub use extension::{DecodedPath, OriginalPath, SanitizeOutcome, SanitizedPath, SkipSanitize};
+pub use normalize::{SanitizeClass, CONTROL_CHARS, CONTROL_WHITESPACE, DANGEROUS_SEGMENTS};
+pub use report::{sanitize_report, SanitizeReport, SegmentChange};
+#[cfg(feature = "json")]
+pub use response::ProblemJson;
+pub use response::{DefaultRejection, RejectResponse, ResponseBody};
+
+use audit::Outcome;
+use extension::SanitizedWith;
+use future::ResponseFuture;
+use normalize::{
+    clean_query, normalize_default, normalize_default_into, normalize_with, Normalized, PathSource,
+};
+use report::Changes;
+use response::status_response;
 
 /// Layer that applies [`SanitizePath`] which sanitizes paths.
 ///
 /// See the [module docs](self) for more details.
-pub struct SanitizePathLayer;
+#[derive(Clone, Debug, Default)]
+pub struct SanitizePathLayer {
+    config: Arc<SanitizeConfig>,
+}
+
+impl SanitizePathLayer {
+    /// Create a layer using the default [`SanitizeConfig`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a layer using the given [`SanitizeConfig`].
+    ///
+    /// The config is not validated, see [`SanitizeConfig::build`] for a checked alternative.
+    pub fn with_config(config: SanitizeConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+        }
+    }
+
+    /// Wrap `layers` in this layer, so paths are sanitized before any of them runs.
+    ///
+    /// Routing, authorization and anything else looking at the path must see the sanitized one,
+    /// but a layer added later to a `ServiceBuilder` runs later, which is easy to get wrong:
+    ///
+    /// ```
+    /// use http::Request;
+    /// use tower::{util::MapRequestLayer, ServiceBuilder};
+    /// use tower_sanitize_path::SanitizePathLayer;
+    ///
+    /// let auth = MapRequestLayer::new(|request: Request<()>| {
+    ///     // Sees `/admin` for `/public/../admin`
+    ///     request
+    /// });
+    ///
+    /// let service = ServiceBuilder::new()
+    ///     .layer(SanitizePathLayer::new().around(auth))
+    ///     .service_fn(|_: Request<()>| async { Ok::<_, std::convert::Infallible>(()) });
+    /// ```
+    ///
+    /// Declare the extensions set by such layers with
+    /// [`SanitizeConfig::path_dependent_extension`] to catch misplaced layers in debug builds with
+    /// the `tracing` feature.
+    pub fn around<L>(self, layers: L) -> Stack<L, Self> {
+        Stack::new(layers, self)
+    }
+}
 
 impl<S> Layer<S> for SanitizePathLayer {
     type Service = SanitizePath<S>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        SanitizePath::sanitize_paths(inner)
+        SanitizePath {
+            inner,
+            config: self.config.clone(),
+            reject: DefaultRejection,
+        }
     }
 }
 
 /// Middleware to remove filesystem path traversals attempts from URL paths.
 ///
 /// See the [module docs](self) for more details.
-#[derive(Clone, Copy, Debug)]
-pub struct SanitizePath<S> {
+///
+/// Nesting `SanitizePath` middlewares, like a global layer and a route specific one, is safe. A
+/// request rewritten by a `SanitizePath` is skipped by any inner `SanitizePath` with an equal
+/// config, so it is never decoded twice. For the same reason, sending a rewritten request through
+/// again, like a retry does, leaves its URI and extensions as they are. A middleware in between
+/// changing the path, like one stripping a prefix, makes the inner `SanitizePath` sanitize it
+/// again.
+///
+/// The config is shared behind an [`Arc`], so cloning the service is cheap and requests only
+/// ever borrow it.
+///
+/// Responses to rejected requests are built by `R`, see [`RejectResponse`].
+#[derive(Clone)]
+pub struct SanitizePath<S, R = DefaultRejection> {
     inner: S,
+    config: Arc<SanitizeConfig>,
+    reject: R,
 }
 
 impl<S> SanitizePath<S> {
@@ -38,7 +136,75 @@ impl<S> SanitizePath<S> {
     ///
     /// This will make all paths on the URL safe for the service to consume.
     pub fn sanitize_paths(inner: S) -> Self {
-        Self { inner }
+        Self::with_config(inner, SanitizeConfig::default())
+    }
+
+    /// Sanitize all paths for the given service using the given [`SanitizeConfig`].
+    ///
+    /// The config is not validated, see [`SanitizeConfig::validate`].
+    pub fn with_config(inner: S, config: SanitizeConfig) -> Self {
+        Self {
+            inner,
+            config: Arc::new(config),
+            reject: DefaultRejection,
+        }
+    }
+
+    /// Sanitize all paths for the given service, rejecting paths longer than `max_len` bytes or
+    /// with more than `max_segments` segments.
+    ///
+    /// A shorthand for [`SanitizeConfig::max_path_len`] and [`SanitizeConfig::max_segments`],
+    /// which respond with `414 URI Too Long` and `400 Bad Request`.
+    pub fn sanitize_paths_with_limits(inner: S, max_len: usize, max_segments: usize) -> Self {
+        let config = SanitizeConfig::new()
+            .max_path_len(max_len)
+            .max_segments(max_segments);
+
+        Self::with_config(inner, config)
+    }
+
+    /// Sanitize all paths for the given service with `config`, building the responses to
+    /// rejected requests with `builder`.
+    ///
+    /// ```
+    /// use http::{Request, Response, StatusCode};
+    /// use tower::service_fn;
+    /// use tower_sanitize_path::{SanitizeClass, SanitizeConfig, SanitizePath};
+    ///
+    /// # let inner = service_fn(|_: Request<()>| async {
+    /// #     Ok::<_, std::convert::Infallible>(Response::new(()))
+    /// # });
+    /// let config = SanitizeConfig::new().reject_on_traversal(true);
+    /// let teapot = |_req: &Request<()>, status: StatusCode, _class: SanitizeClass| {
+    ///     let status = match status {
+    ///         StatusCode::BAD_REQUEST => StatusCode::IM_A_TEAPOT,
+    ///         status => status,
+    ///     };
+    ///
+    ///     Response::builder().status(status).body(()).unwrap()
+    /// };
+    /// let service = SanitizePath::with_reject_response_builder(inner, config, teapot);
+    /// ```
+    pub fn with_reject_response_builder<F>(
+        inner: S,
+        config: SanitizeConfig,
+        builder: F,
+    ) -> SanitizePath<S, F> {
+        Self::with_config(inner, config).reject_response_builder(builder)
+    }
+}
+
+impl<S, R> SanitizePath<S, R> {
+    /// Build the responses to rejected requests with `builder` instead.
+    ///
+    /// Any [`RejectResponse`] can be used, usually a closure taking the rejected request, the
+    /// status it would be rejected with and its [`SanitizeClass`].
+    pub fn reject_response_builder<F>(self, builder: F) -> SanitizePath<S, F> {
+        SanitizePath {
+            inner: self.inner,
+            config: self.config,
+            reject: builder,
+        }
     }
 
     /// Access the wrapped service.
@@ -47,68 +213,565 @@ impl<S> SanitizePath<S> {
     }
 }
 
-impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for SanitizePath<S>
+impl<S: fmt::Debug, R> fmt::Debug for SanitizePath<S, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SanitizePath")
+            .field("inner", &self.inner)
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, R, ReqBody, ResBody> Service<Request<ReqBody>> for SanitizePath<S, R>
 where
     S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    R: RejectResponse<ReqBody, ResBody>,
+    ResBody: ResponseBody,
 {
     type Response = S::Response;
     type Error = S::Error;
-    type Future = S::Future;
+    type Future = ResponseFuture<S::Future, ResBody>;
 
     #[inline]
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         self.inner.poll_ready(cx)
     }
 
-    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
-        sanitize_path(req.uri_mut());
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        self.sanitize(req, &mut Outcome::default())
+    }
+}
+
+impl<S, R> SanitizePath<S, R> {
+    /// Handle `req` like [`Service::call`], noting what happened to it in `outcome`.
+    pub(crate) fn sanitize<ReqBody, ResBody>(
+        &mut self,
+        mut req: Request<ReqBody>,
+        outcome: &mut Outcome,
+    ) -> ResponseFuture<S::Future, ResBody>
+    where
+        S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+        R: RejectResponse<ReqBody, ResBody>,
+        ResBody: ResponseBody,
+    {
+        let config = &*self.config;
+
+        if req.extensions().get::<SkipSanitize>().is_some() {
+            return ResponseFuture::inner(self.inner.call(req));
+        }
+
+        if let Some(SanitizedWith(outer, path)) = req.extensions().get() {
+            if **outer == *config && req.uri().path() == path {
+                return ResponseFuture::inner(self.inner.call(req));
+            }
+        }
+
+        // Without `tracing` there is nothing to warn with
+        #[cfg(all(debug_assertions, feature = "tracing"))]
+        for extension in &config.path_dependent {
+            if (extension.is_set)(req.extensions()) {
+                trace::misplaced(extension.name);
+            }
+        }
+
+        let sanitized = match timed_sanitized_uri(config, req.uri()) {
+            Ok(sanitized) => sanitized,
+            Err(Rejected { status, class }) => {
+                return refuse(
+                    &mut self.inner,
+                    &self.reject,
+                    config,
+                    req,
+                    status,
+                    class,
+                    outcome,
+                );
+            }
+        };
+
+        if !config.serves_authority(req.uri()) {
+            let (status, class) = (
+                StatusCode::MISDIRECTED_REQUEST,
+                SanitizeClass::MisdirectedAuthority,
+            );
+
+            return refuse(
+                &mut self.inner,
+                &self.reject,
+                config,
+                req,
+                status,
+                class,
+                outcome,
+            );
+        }
+
+        let path = sanitized
+            .as_ref()
+            .map_or(req.uri().path(), |sanitized| sanitized.uri.path());
+        if config.denies(path) {
+            let (status, class) = (StatusCode::FORBIDDEN, SanitizeClass::Denied);
+
+            return refuse(
+                &mut self.inner,
+                &self.reject,
+                config,
+                req,
+                status,
+                class,
+                outcome,
+            );
+        }
+
+        if config.exceeds_fs_path_len(path) {
+            let (status, class) = (StatusCode::BAD_REQUEST, SanitizeClass::FsPathTooLong);
+
+            return refuse(
+                &mut self.inner,
+                &self.reject,
+                config,
+                req,
+                status,
+                class,
+                outcome,
+            );
+        }
+
+        let Sanitized {
+            uri,
+            class,
+            decoded,
+            changes,
+        } = match sanitized {
+            Some(sanitized) => sanitized,
+            None => return ResponseFuture::inner(self.inner.call(req)),
+        };
+        let decoded = config
+            .record_decoded_path
+            .then(|| DecodedPath(decoded.into_owned()));
+
+        if config.dry_run {
+            observe("dry_run", &req, class, outcome);
+            req.extensions_mut().insert(class);
+
+            return ResponseFuture::inner(self.inner.call(req));
+        }
+
+        if let Some(hook) = &config.rewrite_hook {
+            match hook.decide(req.uri().path(), uri.path()) {
+                RewriteDecision::Apply => {}
+                RewriteDecision::Reject(status) => {
+                    observe("reject", &req, class, outcome);
+                    let response = self.reject.reject(&req, config, status, class);
+
+                    return ResponseFuture::respond(response);
+                }
+                RewriteDecision::KeepOriginal => {
+                    observe("keep", &req, class, outcome);
+
+                    return ResponseFuture::inner(self.inner.call(req));
+                }
+            }
+        }
+
+        if config.reject_on_traversal && class == SanitizeClass::ParentDir {
+            observe("reject", &req, class, outcome);
+            let response = self
+                .reject
+                .reject(&req, config, StatusCode::BAD_REQUEST, class);
+
+            return ResponseFuture::respond(response);
+        }
+
+        if config.skips_websocket(&req) {
+            observe("keep", &req, class, outcome);
+
+            return ResponseFuture::inner(self.inner.call(req));
+        }
+
+        let follow_up = match &config.redirect_loop_guard {
+            Some(marker) => req.headers().contains_key(marker),
+            None => false,
+        };
+        if config.redirect && !follow_up {
+            observe("redirect", &req, class, outcome);
+            let mut response = status_response(config.redirect_status_or_default());
+
+            if let Ok(location) = HeaderValue::try_from(uri.to_string()) {
+                response.headers_mut().insert(header::LOCATION, location);
+            }
+            if let Some(marker) = &config.redirect_loop_guard {
+                let headers = response.headers_mut();
+                headers.insert(marker.clone(), HeaderValue::from_static("1"));
+            }
+
+            return ResponseFuture::respond(response);
+        }
+
+        let recorded = changes.map(|changes| SanitizeOutcome {
+            class,
+            original: req.uri().path().to_owned(),
+            removed: changes.segments,
+            decode_rounds: changes.decode_rounds,
+        });
+
+        // Only requests reaching the inner service with a rewritten URI get the response header
+        let response_header = if config.preserve_original_in_uri {
+            observe("preserve", &req, class, outcome);
+            req.extensions_mut()
+                .insert(SanitizedPath(uri.path().to_owned()));
+
+            None
+        } else {
+            observe("rewrite", &req, class, outcome);
+            let original = OriginalPath(req.uri().path().to_owned());
+            *req.uri_mut() = uri;
+            outcome.rewritten(req.uri());
+
+            if req.extensions().get::<OriginalPath>().is_none() {
+                req.extensions_mut().insert(original);
+            }
+
+            config.response_header.as_ref()
+        };
+
+        let path = req.uri().path().to_owned();
+        let extensions = req.extensions_mut();
+        extensions.insert(SanitizedWith(self.config.clone(), path));
+        if let Some(decoded) = decoded {
+            extensions.insert(decoded);
+        }
+        if let Some(recorded) = recorded {
+            extensions.insert(recorded);
+        }
+
+        let future = self.inner.call(req);
+        match response_header {
+            Some(header) => ResponseFuture::annotate(future, header.clone()),
+            None => ResponseFuture::inner(future),
+        }
+    }
+}
+
+/// Record that `req` needed sanitizing because of `class`, and what `action` was taken.
+fn observe<B>(action: &'static str, req: &Request<B>, class: SanitizeClass, outcome: &mut Outcome) {
+    trace::sanitized(action, req.uri().path(), class);
+    outcome.note(class);
+}
+
+/// Sanitize the path of `uri` with the default [`SanitizeConfig`].
+///
+/// Returns the sanitized URI along with why it was changed, or `None` if the path was already
+/// clean. The default config never rejects paths.
+///
+/// ```
+/// use tower_sanitize_path::{classify_and_sanitize, SanitizeClass};
+///
+/// let (uri, class) = classify_and_sanitize("/../secret".parse().unwrap());
+///
+/// assert_eq!(uri, "/secret");
+/// assert_eq!(class, Some(SanitizeClass::ParentDir));
+/// ```
+pub fn classify_and_sanitize(mut uri: Uri) -> (Uri, Option<SanitizeClass>) {
+    let class = sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
+
+    (uri, class)
+}
+
+/// Like [`classify_and_sanitize`], but returns an error instead of the original URI if the
+/// sanitized path cannot be put back into a valid URI.
+pub fn try_classify_and_sanitize(uri: Uri) -> Result<(Uri, Option<SanitizeClass>), SanitizeError> {
+    let Some(normalized) = normalize_default(uri.path(), PathSource::Uri, None) else {
+        return Ok((uri, None));
+    };
+
+    let Sanitized { uri, class, .. } = rebuild(&uri, normalized, None)?;
+
+    Ok((uri, Some(class)))
+}
+
+/// Find the paths the default [`SanitizeConfig`] would change, returning each along with its
+/// sanitized form.
+///
+/// Useful at startup to check a route table only holds canonical paths, which sanitization never
+/// changes at runtime.
+///
+/// ```
+/// use tower_sanitize_path::validate_paths;
+///
+/// let routes = ["/", "/users/:id", "/static//*path", "/admin/../login"];
+///
+/// assert_eq!(
+///     validate_paths(routes),
+///     [
+///         ("/static//*path", "/static/*path".to_string()),
+///         ("/admin/../login", "/login".to_string()),
+///     ]
+/// );
+/// ```
+pub fn validate_paths<'a>(paths: impl IntoIterator<Item = &'a str>) -> Vec<(&'a str, String)> {
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let normalized = normalize_default(path, PathSource::Str, None)?;
+            Some((path, normalized.path))
+        })
+        .collect()
+}
+
+/// Sanitize `path` with the default [`SanitizeConfig`] into `out`, returning whether it changed.
+///
+/// `out` is cleared and then holds the sanitized path, or a copy of `path` if it was already
+/// clean. Reusing the same buffer across calls saves allocating the sanitized path every time.
+///
+/// This is not allocation-free: paths needing sanitizing still allocate while being decoded and
+/// split into segments. Only clean paths are handled without allocating once `out` is large
+/// enough.
+///
+/// ```
+/// use tower_sanitize_path::sanitize_into;
+///
+/// let mut out = String::new();
+/// for (path, sanitized) in [("/a/../b", "/b"), ("/c", "/c")] {
+///     sanitize_into(path, &mut out);
+///     assert_eq!(out, sanitized);
+/// }
+/// ```
+pub fn sanitize_into(path: &str, out: &mut String) -> bool {
+    let changed = normalize_default_into(path, PathSource::Str, None, out).is_some();
+    if !changed {
+        out.push_str(path);
+    }
+
+    changed
+}
+
+/// Whether `a` and `b` name the same resource once their paths are sanitized with the default
+/// [`SanitizeConfig`], for caching or deduplicating requests.
+///
+/// Only the paths and queries are compared, queries as they are. Nothing is allocated when both
+/// paths are already clean.
+///
+/// ```
+/// use tower_sanitize_path::uris_equivalent;
+///
+/// assert!(uris_equivalent(&"/a/../b?q".parse().unwrap(), &"/b?q".parse().unwrap()));
+/// assert!(!uris_equivalent(&"/b?q".parse().unwrap(), &"/b?r".parse().unwrap()));
+/// ```
+pub fn uris_equivalent(a: &Uri, b: &Uri) -> bool {
+    fn sanitized(path: &str) -> Cow<'_, str> {
+        match normalize_default(path, PathSource::Uri, None) {
+            Some(normalized) => Cow::Owned(normalized.path),
+            None => Cow::Borrowed(path),
+        }
+    }
+
+    a.query() == b.query() && sanitized(a.path()) == sanitized(b.path())
+}
+
+/// Wrap a plain request handler, like one given to `hyper::service::service_fn`, so it only sees
+/// request paths sanitized with the default [`SanitizeConfig`].
+///
+/// For servers not using Tower layers. Only the URI is rewritten, no extensions are inserted.
+///
+/// ```
+/// use http::{Request, Response};
+/// use hyper::{service::service_fn, Body};
+/// use std::convert::Infallible;
+/// use tower_sanitize_path::sanitize_then;
+///
+/// async fn handle(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+///     // `req.uri().path()` will not be usable to traverse the filesystem
+///     Ok(Response::new(Body::from(req.uri().to_string())))
+/// }
+///
+/// let service = service_fn(sanitize_then(handle));
+/// ```
+pub fn sanitize_then<F, B, R>(mut handler: F) -> impl FnMut(Request<B>) -> R
+where
+    F: FnMut(Request<B>) -> R,
+{
+    move |mut request| {
+        sanitize_path(&SanitizeConfig::DEFAULT, request.uri_mut());
+        handler(request)
+    }
+}
+
+/// Sanitize `uri` in place, returning why it was changed or rejected.
+fn sanitize_path(config: &SanitizeConfig, uri: &mut Uri) -> Option<SanitizeClass> {
+    match sanitized_uri(config, uri) {
+        Ok(Some(Sanitized {
+            uri: new_uri,
+            class,
+            ..
+        })) => {
+            *uri = new_uri;
+
+            Some(class)
+        }
+        Ok(None) => None,
+        Err(Rejected { class, .. }) => Some(class),
+    }
+}
+
+/// A URI changed by [`sanitized_uri`].
+struct Sanitized<'a> {
+    uri: Uri,
+    class: SanitizeClass,
+    decoded: Cow<'a, str>,
+    changes: Option<Changes>,
+}
+
+/// Respond to `req` with a rejection built by `reject`, or forward it to `inner` with `class` in
+/// its extensions in a [dry run](SanitizeConfig::dry_run).
+fn refuse<S, R, ReqBody, ResBody>(
+    inner: &mut S,
+    reject: &R,
+    config: &SanitizeConfig,
+    mut req: Request<ReqBody>,
+    status: StatusCode,
+    class: SanitizeClass,
+    outcome: &mut Outcome,
+) -> ResponseFuture<S::Future, ResBody>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    R: RejectResponse<ReqBody, ResBody>,
+{
+    if config.dry_run {
+        observe("dry_run", &req, class, outcome);
+        req.extensions_mut().insert(class);
 
-        self.inner.call(req)
+        return ResponseFuture::inner(inner.call(req));
     }
+
+    let action = match class {
+        SanitizeClass::Denied => "deny",
+        _ => "reject",
+    };
+    observe(action, &req, class, outcome);
+    let response = reject.reject(&req, config, status, class);
+
+    ResponseFuture::respond(response)
+}
+
+/// A request which should be rejected instead of forwarded.
+#[derive(Debug, PartialEq, Eq)]
+struct Rejected {
+    status: StatusCode,
+    class: SanitizeClass,
 }
 
-fn sanitize_path(uri: &mut Uri) {
-    let path = uri.path();
-    let path_decoded = decode(path);
+impl Rejected {
+    fn bad_request(class: SanitizeClass) -> Self {
+        let status = match class {
+            SanitizeClass::PathTooLong => StatusCode::URI_TOO_LONG,
+            _ => StatusCode::BAD_REQUEST,
+        };
 
-    // Check if the path contains a trailing slash and that it is not the only
-    // character.
-    let trailing_slash = path_decoded.len() > 1
-        && path_decoded
-            .chars()
-            .last()
-            .filter(|char| *char == '/')
-            .is_some();
+        Self { status, class }
+    }
+}
 
-    let path_buf = PathBuf::from_str(&path_decoded).expect("infallible");
+/// Get the sanitized version of `uri`, `None` if it is already clean or why it should be
+/// rejected.
+fn sanitized_uri<'a>(
+    config: &SanitizeConfig,
+    uri: &'a Uri,
+) -> Result<Option<Sanitized<'a>>, Rejected> {
+    let mut changes = config.record_outcome.then(Changes::default);
+    let normalized = normalize_with(config, uri.path(), PathSource::Uri, changes.as_mut())
+        .map_err(Rejected::bad_request)?;
+
+    let query = match uri.query() {
+        Some(query) if normalized.is_some() || config.query_control_always => {
+            clean_query(config.query_control, query).map_err(Rejected::bad_request)?
+        }
+        _ => None,
+    };
 
-    let mut new_path = path_buf
-        .components()
-        .filter(|c| matches!(c, Component::RootDir | Component::Normal(_)))
-        .collect::<PathBuf>()
-        .display()
-        .to_string();
+    let normalized = match (normalized, &query) {
+        (Some(mut normalized), Some(_)) => {
+            normalized.class = normalized.class.max(SanitizeClass::QueryControl);
+            normalized
+        }
+        (Some(normalized), None) => normalized,
+        (None, Some(_)) => Normalized {
+            path: uri.path().to_owned(),
+            class: SanitizeClass::QueryControl,
+            decoded: Cow::Borrowed(uri.path()),
+        },
+        (None, None) => return Ok(None),
+    };
+    let class = normalized.class;
 
-    // Path::components above will normalize away the trailing slash if there is one,
-    // so we add it back.
-    if trailing_slash {
-        new_path += "/";
+    match rebuild(uri, normalized, query.as_deref()) {
+        Ok(sanitized) => Ok(Some(Sanitized {
+            changes,
+            ..sanitized
+        })),
+        Err(_) => reconstruct_failed(config, class),
     }
+}
 
-    if path == new_path {
-        return;
+/// [`sanitized_uri`], recording how long it took with the `tracing` feature.
+fn timed_sanitized_uri<'a>(
+    config: &SanitizeConfig,
+    uri: &'a Uri,
+) -> Result<Option<Sanitized<'a>>, Rejected> {
+    let timer = trace::Timer::start();
+    let sanitized = sanitized_uri(config, uri);
+    timer.finish(uri.path());
+
+    sanitized
+}
+
+/// Apply [`SanitizeConfig::on_reconstruct_error`] for a path sanitized because of `class`.
+fn reconstruct_failed(
+    config: &SanitizeConfig,
+    class: SanitizeClass,
+) -> Result<Option<Sanitized<'static>>, Rejected> {
+    match config.on_reconstruct_error {
+        OnReconstructError::Passthrough => Ok(None),
+        OnReconstructError::Reject(status) => Err(Rejected { status, class }),
     }
+}
 
+/// Put the normalized path back into `uri`, along with `new_query` if given.
+fn rebuild<'a>(
+    uri: &Uri,
+    normalized: Normalized<'a>,
+    new_query: Option<&str>,
+) -> Result<Sanitized<'a>, SanitizeError> {
+    let Normalized {
+        path: new_path,
+        class,
+        decoded,
+    } = normalized;
+
+    Ok(Sanitized {
+        uri: replace_path(uri, new_path, new_query)?,
+        class,
+        decoded,
+        changes: None,
+    })
+}
+
+/// Replace the path of `uri`, keeping its query unless `new_query` is given.
+fn replace_path(
+    uri: &Uri,
+    new_path: String,
+    new_query: Option<&str>,
+) -> Result<Uri, SanitizeError> {
     let mut parts = uri.clone().into_parts();
 
     let new_path_and_query = if let Some(path_and_query) = parts.path_and_query {
-        let new_path_and_query = if let Some(query) = path_and_query.query() {
+        let new_path_and_query = if let Some(query) = new_query.or(path_and_query.query()) {
             Cow::Owned(format!("{new_path}?{query}"))
         } else {
             new_path.into()
         }
         .parse()
-        .expect("url to still be valid");
+        .map_err(SanitizeError::InvalidPath)?;
 
         Some(new_path_and_query)
     } else {
@@ -116,19 +779,31 @@ fn sanitize_path(uri: &mut Uri) {
     };
 
     parts.path_and_query = new_path_and_query;
-    if let Ok(new_uri) = Uri::from_parts(parts) {
-        *uri = new_uri;
-    }
+
+    Uri::from_parts(parts).map_err(SanitizeError::InvalidUri)
 }
 
 #[cfg(test)]
 mod tests {
     use std::convert::Infallible;
 
-    use tower::{ServiceBuilder, ServiceExt};
+    use http::HeaderName;
+    use static_assertions::assert_impl_all;
+    use tower::{
+        util::{BoxCloneService, MapRequestLayer},
+        ServiceBuilder, ServiceExt,
+    };
 
     use super::*;
 
+    type Inner = BoxCloneService<Request<()>, Response<String>, Infallible>;
+
+    assert_impl_all!(SanitizeConfig: Send, Sync, Clone);
+    assert_impl_all!(SanitizePathLayer: Send, Sync, Clone);
+    assert_impl_all!(SanitizePath<Inner>: Send, Clone);
+    assert_impl_all!(SanitizePath<()>: Send, Sync, Clone);
+    assert_impl_all!(ResponseFuture<<Inner as Service<Request<()>>>::Future, String>: Send);
+
     #[tokio::test]
     async fn layer() {
         async fn handle(request: Request<()>) -> Result<Response<String>, Infallible> {
@@ -136,7 +811,7 @@ mod tests {
         }
 
         let mut svc = ServiceBuilder::new()
-            .layer(SanitizePathLayer)
+            .layer(SanitizePathLayer::new())
             .service_fn(handle);
 
         let body = svc
@@ -154,23 +829,141 @@ mod tests {
     #[test]
     fn no_path() {
         let mut uri = "/".parse().unwrap();
-        sanitize_path(&mut uri);
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
 
         assert_eq!(uri, "/");
     }
 
+    #[test]
+    fn validate_route_table() {
+        let routes = [
+            "/",
+            "/health",
+            "/api/v1/users/:id",
+            "/api/v1/users/{id}/posts/",
+            "/api/v1/./files/*rest",
+            "/assets/*path",
+        ];
+
+        assert_eq!(
+            validate_paths(routes),
+            [("/api/v1/./files/*rest", "/api/v1/files/*rest".to_string())]
+        );
+    }
+
+    #[test]
+    fn sanitize_into_reuses_buffer() {
+        let mut out = String::with_capacity(64);
+        let buffer = out.as_ptr();
+
+        assert!(sanitize_into("/a/../b", &mut out));
+        assert_eq!(out, "/b");
+
+        assert!(!sanitize_into("/already/clean", &mut out));
+        assert_eq!(out, "/already/clean");
+
+        assert!(sanitize_into("/%2e%2e/x//y/./z", &mut out));
+        assert_eq!(out, "/x/y/z");
+
+        assert!(sanitize_into("/../../", &mut out));
+        assert_eq!(out, "/");
+
+        assert!(!sanitize_into("", &mut out));
+        assert_eq!(out, "");
+
+        assert_eq!(out.as_ptr(), buffer);
+    }
+
+    #[test]
+    fn equivalent_uris() {
+        let equivalent =
+            |a: &str, b: &str| uris_equivalent(&a.parse().unwrap(), &b.parse().unwrap());
+
+        assert!(equivalent("/a/../b", "/b"));
+        assert!(equivalent("/b", "/a/../b"));
+        assert!(equivalent("/%2e%2e/b//c", "/x/../b/c"));
+        assert!(equivalent("/b?q=1", "/a/./../b?q=1"));
+        assert!(equivalent("/b", "/b"));
+
+        assert!(!equivalent("/b?q=1", "/b?q=2"));
+        assert!(!equivalent("/b?q=1", "/b"));
+        assert!(!equivalent("/a/../b?q", "/b?r"));
+        assert!(!equivalent("/a/b", "/a/c"));
+        assert!(!equivalent("/a", "/a/"));
+    }
+
     #[test]
     fn maintain_query() {
         let mut uri = "/?test".parse().unwrap();
-        sanitize_path(&mut uri);
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
 
         assert_eq!(uri, "/?test");
     }
 
+    #[test]
+    fn encoded_query_delimiters() {
+        let configs = [
+            SanitizeConfig::new(),
+            SanitizeConfig::new().decode_rounds(3),
+            SanitizeConfig::new().query_control_policy(QueryControlPolicy::Strip),
+            SanitizeConfig::new()
+                .query_control_policy(QueryControlPolicy::Strip)
+                .query_control_always(true),
+        ];
+
+        for config in &configs {
+            let mut uri: Uri = "/a/../b?x=%26%3D%23&y=%2e%2e%2f%252e".parse().unwrap();
+            assert_eq!(
+                sanitize_path(config, &mut uri),
+                Some(SanitizeClass::ParentDir)
+            );
+            assert_eq!(uri.path(), "/b");
+            assert_eq!(uri.query(), Some("x=%26%3D%23&y=%2e%2e%2f%252e"));
+
+            let mut uri: Uri = "http://example.com/%2e%2e/b?x=%26%3d%23".parse().unwrap();
+            sanitize_path(config, &mut uri);
+            assert_eq!(uri, "http://example.com/b?x=%26%3d%23");
+        }
+    }
+
+    #[test]
+    fn root_empty_query() {
+        let mut uri = "/?".parse().unwrap();
+        assert_eq!(sanitize_path(&SanitizeConfig::DEFAULT, &mut uri), None);
+        assert_eq!(uri, "/?");
+
+        let mut uri: Uri = "//?".parse().unwrap();
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
+        assert_eq!(uri, "/?");
+        assert_eq!(uri.query(), Some(""));
+    }
+
+    #[test]
+    fn root_double_slash_query() {
+        let mut uri: Uri = "//?x".parse().unwrap();
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
+
+        assert_eq!(uri, "/?x");
+        assert_eq!(uri.path(), "/");
+        assert_eq!(uri.query(), Some("x"));
+    }
+
+    #[test]
+    fn root_fragment() {
+        // Fragments are never sent by clients, and dropped when parsing
+        let mut uri: Uri = "/#frag".parse().unwrap();
+        assert_eq!(sanitize_path(&SanitizeConfig::DEFAULT, &mut uri), None);
+        assert_eq!(uri, "/");
+
+        let mut uri: Uri = "//?x#frag".parse().unwrap();
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
+        assert_eq!(uri, "/?x");
+    }
+
     #[test]
     fn path_maintain_query() {
         let mut uri = "/path?test=true".parse().unwrap();
-        sanitize_path(&mut uri);
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
 
         assert_eq!(uri, "/path?test=true");
     }
@@ -178,7 +971,7 @@ mod tests {
     #[test]
     fn remove_path_parent_traversal() {
         let mut uri = "/../../path".parse().unwrap();
-        sanitize_path(&mut uri);
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
 
         assert_eq!(uri, "/path");
     }
@@ -186,7 +979,7 @@ mod tests {
     #[test]
     fn remove_path_parent_traversal_maintain_query() {
         let mut uri = "/../../path?name=John".parse().unwrap();
-        sanitize_path(&mut uri);
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
 
         assert_eq!(uri, "/path?name=John");
     }
@@ -194,7 +987,7 @@ mod tests {
     #[test]
     fn remove_path_current_traversal() {
         let mut uri = "/.././path".parse().unwrap();
-        sanitize_path(&mut uri);
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
 
         assert_eq!(uri, "/path");
     }
@@ -202,7 +995,7 @@ mod tests {
     #[test]
     fn remove_path_encoded_traversal() {
         let mut uri = "/..%2f..%2fpath".parse().unwrap();
-        sanitize_path(&mut uri);
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
 
         assert_eq!(uri, "/path");
     }
@@ -210,7 +1003,7 @@ mod tests {
     #[test]
     fn keep_trailing_slash() {
         let mut uri = "/path/".parse().unwrap();
-        sanitize_path(&mut uri);
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
 
         assert_eq!(uri, "/path/");
     }
@@ -218,8 +1011,1287 @@ mod tests {
     #[test]
     fn keep_only_one_trailing_slash() {
         let mut uri = "/path//".parse().unwrap();
-        sanitize_path(&mut uri);
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
 
         assert_eq!(uri, "/path/");
     }
+
+    #[test]
+    fn classify_unchanged() {
+        let (uri, class) = classify_and_sanitize("/path/?query".parse().unwrap());
+
+        assert_eq!(uri, "/path/?query");
+        assert_eq!(class, None);
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[test]
+    fn classify_decoded() {
+        let (uri, class) = classify_and_sanitize("/%70ath".parse().unwrap());
+
+        assert_eq!(uri, "/path");
+        assert_eq!(class, Some(SanitizeClass::Decoded));
+    }
+
+    #[test]
+    fn classify_empty_segment() {
+        let (uri, class) = classify_and_sanitize("/a//b?query".parse().unwrap());
+
+        assert_eq!(uri, "/a/b?query");
+        assert_eq!(class, Some(SanitizeClass::EmptySegment));
+    }
+
+    #[test]
+    fn classify_current_dir() {
+        let (uri, class) = classify_and_sanitize("/a/./b".parse().unwrap());
+
+        assert_eq!(uri, "/a/b");
+        assert_eq!(class, Some(SanitizeClass::CurrentDir));
+    }
+
+    #[test]
+    fn classify_parent_dir() {
+        let (uri, class) = classify_and_sanitize("/..%2f.//path".parse().unwrap());
+
+        assert_eq!(uri, "/path");
+        assert_eq!(class, Some(SanitizeClass::ParentDir));
+    }
+
+    #[test]
+    fn root_double_slash() {
+        let mut uri = "//".parse().unwrap();
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
+
+        assert_eq!(uri, "/");
+    }
+
+    #[test]
+    fn resolve_parent_traversal() {
+        let mut uri = "/a/b/../c".parse().unwrap();
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
+
+        assert_eq!(uri, "/a/c");
+    }
+
+    #[test]
+    fn trailing_parent_traversal_maintain_query() {
+        let mut uri = "/foo/..?x=1".parse().unwrap();
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
+
+        assert_eq!(uri, "/?x=1");
+    }
+
+    #[test]
+    fn trailing_current_traversal_maintain_query() {
+        let mut uri = "/foo/.?y=2".parse().unwrap();
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
+
+        assert_eq!(uri, "/foo?y=2");
+    }
+
+    async fn echo(request: Request<()>) -> Result<Response<String>, Infallible> {
+        let class = request.extensions().get::<SanitizeClass>();
+
+        Ok(Response::new(format!("{} {class:?}", request.uri())))
+    }
+
+    async fn call(config: SanitizeConfig, uri: &str) -> Response<String> {
+        ServiceBuilder::new()
+            .layer(config.build().unwrap())
+            .service_fn(echo)
+            .oneshot(Request::builder().uri(uri).body(()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn dry_run() {
+        let config = SanitizeConfig::new().dry_run(true);
+        let response = call(config, "/a/../secret").await;
+
+        assert_eq!(response.into_body(), "/a/../secret Some(ParentDir)");
+
+        // Rejections are only observed as well
+        let config = SanitizeConfig::new()
+            .dry_run(true)
+            .deny_paths(["/admin"])
+            .max_path_len(16)
+            .expected_hosts(["example.com"]);
+
+        let response = call(config.clone(), "/admin").await;
+        assert_eq!(response.into_body(), "/admin Some(Denied)");
+
+        let response = call(config.clone(), "/a/b/c/d/e/f/g/h/i").await;
+        assert_eq!(response.into_body(), "/a/b/c/d/e/f/g/h/i Some(PathTooLong)");
+
+        let response = call(config, "http://other.com/a").await;
+        assert_eq!(
+            response.into_body(),
+            "http://other.com/a Some(MisdirectedAuthority)"
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_on_traversal() {
+        let config = SanitizeConfig::new().reject_on_traversal(true);
+
+        let response = call(config.clone(), "/a/../secret").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.into_body(), "");
+
+        let response = call(config.clone(), "/a//secret").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.into_body(), "/a/secret None");
+
+        // Not hidden by clamping to the virtual root
+        let config = config.virtual_root("/app");
+        let response = call(config.clone(), "/app/../../etc").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = call(config, "/etc").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.into_body(), "/app None");
+    }
+
+    #[tokio::test]
+    async fn redirect() {
+        let config = SanitizeConfig::new().redirect(true);
+        let response = call(config, "/a/../b?q=1").await;
+
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()[header::LOCATION], "/b?q=1");
+    }
+
+    #[tokio::test]
+    async fn redirect_status() {
+        let config = SanitizeConfig::new()
+            .redirect(true)
+            .redirect_status(StatusCode::MOVED_PERMANENTLY);
+        let response = call(config, "/a/../b").await;
+
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(response.headers()[header::LOCATION], "/b");
+    }
+
+    #[tokio::test]
+    async fn redirect_loop_guard() {
+        let marker = HeaderName::from_static("x-sanitize-redirected");
+        let svc = ServiceBuilder::new()
+            .layer(
+                SanitizeConfig::new()
+                    .redirect(true)
+                    .redirect_loop_guard(marker.clone())
+                    .build()
+                    .unwrap(),
+            )
+            .service_fn(echo);
+
+        let request = Request::builder().uri("/a/../b").body(()).unwrap();
+        let response = svc.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()[header::LOCATION], "/b");
+        assert_eq!(response.headers()[&marker], "1");
+
+        // Following the redirect, but dirtying the path again
+        let location = response.headers()[header::LOCATION].to_str().unwrap();
+        let request = Request::builder()
+            .uri(format!("/x/..{location}"))
+            .header(&marker, &response.headers()[&marker])
+            .body(())
+            .unwrap();
+        let response = svc.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.into_body(), "/b None");
+
+        // Without the guard the client is redirected again
+        let response = call(SanitizeConfig::new().redirect(true), "/x/../b").await;
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert!(!response.headers().contains_key(&marker));
+    }
+
+    #[tokio::test]
+    async fn redirect_clean_path_forwards() {
+        let config = SanitizeConfig::new().redirect(true);
+        let response = call(config, "/b").await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.into_body(), "/b None");
+    }
+
+    #[tokio::test]
+    async fn preserve_original_in_uri() {
+        let config = SanitizeConfig::new()
+            .preserve_original_in_uri(true)
+            .response_header(
+                HeaderName::from_static("x-path-sanitized"),
+                HeaderValue::from_static("true"),
+            );
+        let service =
+            config
+                .build()
+                .unwrap()
+                .layer(tower::service_fn(|req: Request<()>| async move {
+                    let sanitized = req.extensions().get::<SanitizedPath>();
+                    let original = req.extensions().get::<OriginalPath>();
+                    let body = format!("{} {sanitized:?} {original:?}", req.uri());
+
+                    Ok::<_, Infallible>(Response::new(body))
+                }));
+
+        let request = Request::builder().uri("/a/../secret?q=1").body(()).unwrap();
+        let response = service.oneshot(request).await.unwrap();
+
+        assert!(response.headers().get("x-path-sanitized").is_none());
+        assert_eq!(
+            response.into_body(),
+            r#"/a/../secret?q=1 Some(SanitizedPath("/secret")) None"#
+        );
+    }
+
+    #[tokio::test]
+    async fn reject_response_builder() {
+        let config = SanitizeConfig::new()
+            .reject_on_traversal(true)
+            .deny_paths(["/admin"]);
+        let builder = |req: &Request<()>, status: StatusCode, class: SanitizeClass| {
+            let status = match status {
+                StatusCode::BAD_REQUEST => StatusCode::IM_A_TEAPOT,
+                status => status,
+            };
+
+            Response::builder()
+                .status(status)
+                .body(format!("{} {}", req.uri(), class.description()))
+                .unwrap()
+        };
+        let service =
+            SanitizePath::with_reject_response_builder(tower::service_fn(echo), config, builder);
+
+        let request = Request::builder().uri("/a/../secret").body(()).unwrap();
+        let response = service.clone().oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::IM_A_TEAPOT);
+        assert_eq!(response.into_body(), "/a/../secret path traversal detected");
+
+        // Other statuses can be told apart
+        let request = Request::builder().uri("/admin").body(()).unwrap();
+        let response = service.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let request = Request::builder().uri("/secret").body(()).unwrap();
+        let response = service.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn query_control_policy() {
+        let uri = "/a/../b?x=%00y&z=%0a";
+
+        let response = call(SanitizeConfig::new(), uri).await;
+        assert_eq!(response.into_body(), "/b?x=%00y&z=%0a None");
+
+        let config = SanitizeConfig::new().query_control_policy(QueryControlPolicy::Strip);
+        let response = call(config.clone(), uri).await;
+        assert_eq!(response.into_body(), "/b?x=y&z= None");
+
+        // Only requests with a rewritten path are touched by default
+        let response = call(config.clone(), "/b?x=%00y").await;
+        assert_eq!(response.into_body(), "/b?x=%00y None");
+
+        let response = call(config.query_control_always(true), "/b?x=%00y").await;
+        assert_eq!(response.into_body(), "/b?x=y None");
+
+        let config = SanitizeConfig::new().query_control_policy(QueryControlPolicy::Reject);
+        let response = call(config.clone(), uri).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = call(config.query_control_always(true), "/b?z=%0A").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn rewrite_hook() {
+        let config = SanitizeConfig::new().rewrite_hook(|original, sanitized| {
+            match original.split('/').nth(1) {
+                Some("keep") => RewriteDecision::KeepOriginal,
+                Some("reject") => RewriteDecision::Reject(StatusCode::FORBIDDEN),
+                _ => {
+                    assert_eq!(sanitized, "/secret");
+                    RewriteDecision::Apply
+                }
+            }
+        });
+
+        let response = call(config.clone(), "/apply/../secret").await;
+        assert_eq!(response.into_body(), "/secret None");
+
+        let response = call(config.clone(), "/reject/../secret").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = call(config.clone(), "/keep/../secret").await;
+        assert_eq!(response.into_body(), "/keep/../secret None");
+
+        // Clean paths never reach the hook
+        let response = call(config, "/keep/a").await;
+        assert_eq!(response.into_body(), "/keep/a None");
+    }
+
+    #[tokio::test]
+    async fn response_header() {
+        let config = SanitizeConfig::new().response_header(
+            HeaderName::from_static("x-path-sanitized"),
+            HeaderValue::from_static("true"),
+        );
+
+        let response = call(config.clone(), "/a/../secret").await;
+        assert_eq!(response.headers()["x-path-sanitized"], "true");
+        assert_eq!(response.into_body(), "/secret None");
+
+        let response = call(config.clone(), "/secret").await;
+        assert!(response.headers().get("x-path-sanitized").is_none());
+
+        let response = call(config.clone().reject_on_traversal(true), "/a/../secret").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response.headers().get("x-path-sanitized").is_none());
+
+        // The inner service got the original URI, nothing was rewritten
+        let response = call(config.preserve_original_in_uri(true), "/a/../secret").await;
+        assert!(response.headers().get("x-path-sanitized").is_none());
+        assert_eq!(response.into_body(), "/a/../secret None");
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[test]
+    fn double_encoded_traversal() {
+        let mut uri = "/%252e%252e/secret".parse().unwrap();
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
+        assert_eq!(uri, "/%252e%252e/secret");
+
+        let mut uri = "/%252e%252e/secret".parse().unwrap();
+        sanitize_path(&SanitizeConfig::new().decode_rounds(2), &mut uri);
+        assert_eq!(uri, "/secret");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn spawn_across_threads() {
+        let svc = ServiceBuilder::new()
+            .layer(SanitizePathLayer::new())
+            .service_fn(echo);
+
+        let handles = (0..8).map(|i| {
+            let svc = svc.clone();
+
+            tokio::spawn(async move {
+                let request = Request::builder()
+                    .uri(format!("/{i}/../../secret"))
+                    .body(())
+                    .unwrap();
+
+                svc.oneshot(request).await.unwrap().into_body()
+            })
+        });
+
+        for handle in handles {
+            assert_eq!(handle.await.unwrap(), "/secret None");
+        }
+    }
+
+    #[test]
+    fn nginx_merge_slashes_keep_query() {
+        let config = SanitizeConfig::new().slash_mode(SlashMode::NginxMerge);
+        let mut uri = "//a//b?c=//d".parse().unwrap();
+        sanitize_path(&config, &mut uri);
+
+        assert_eq!(uri, "/a/b?c=//d");
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[tokio::test]
+    async fn path_extensions() {
+        async fn handle(request: Request<()>) -> Result<Response<String>, Infallible> {
+            let original = request.extensions().get::<OriginalPath>().unwrap();
+            let decoded = request.extensions().get::<DecodedPath>().unwrap();
+
+            Ok(Response::new(format!(
+                "{} {} {}",
+                original.0,
+                decoded.0,
+                request.uri()
+            )))
+        }
+
+        let body = ServiceBuilder::new()
+            .layer(SanitizePathLayer::with_config(
+                SanitizeConfig::new().record_decoded_path(true),
+            ))
+            .service_fn(handle)
+            .oneshot(
+                Request::builder()
+                    .uri("/a//b/%2e%2e/%2e/%2e%2E%2fsecret%21?q")
+                    .body(())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .into_body();
+
+        assert_eq!(
+            body,
+            "/a//b/%2e%2e/%2e/%2e%2E%2fsecret%21 /a//b/.././../secret! /secret!?q"
+        );
+    }
+
+    #[tokio::test]
+    async fn no_decoded_path_by_default() {
+        async fn handle(request: Request<()>) -> Result<Response<String>, Infallible> {
+            assert!(request.extensions().get::<OriginalPath>().is_some());
+            assert!(request.extensions().get::<DecodedPath>().is_none());
+
+            Ok(Response::new(String::new()))
+        }
+
+        ServiceBuilder::new()
+            .layer(SanitizePathLayer::new())
+            .service_fn(handle)
+            .oneshot(Request::builder().uri("/a/../b").body(()).unwrap())
+            .await
+            .unwrap();
+    }
+
+    /// Resolves on its second poll, counting how often it was polled.
+    struct CountPolls(Arc<std::sync::atomic::AtomicUsize>);
+
+    impl std::future::Future for CountPolls {
+        type Output = Result<Response<String>, Infallible>;
+
+        fn poll(self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            use std::sync::atomic::Ordering;
+
+            if self.0.fetch_add(1, Ordering::SeqCst) == 0 {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            Poll::Ready(Ok(Response::new(String::new())))
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Counting(Arc<std::sync::atomic::AtomicUsize>);
+
+    impl Service<Request<()>> for Counting {
+        type Response = Response<String>;
+        type Error = Infallible;
+        type Future = CountPolls;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: Request<()>) -> Self::Future {
+            self.0.store(0, std::sync::atomic::Ordering::SeqCst);
+            CountPolls(self.0.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn pass_through_future_is_inner() {
+        let request = |uri| Request::builder().uri(uri).body(()).unwrap();
+        let mut svc = SanitizePath::sanitize_paths(Counting::default());
+
+        // Both clean and rewritten requests hand over the inner future itself
+        let inner: Result<CountPolls, _> = svc.call(request("/a/b")).into_inner();
+        assert!(inner.is_ok());
+        let inner: Result<CountPolls, _> = svc.call(request("/a/../b")).into_inner();
+        assert!(inner.is_ok());
+
+        // Polled exactly as often as the inner future needs
+        let polls = svc.inner().0.clone();
+        svc.call(request("/a/../b")).await.unwrap();
+        assert_eq!(polls.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        let mut svc = SanitizePath::with_config(
+            Counting::default(),
+            SanitizeConfig::new().reject_on_traversal(true),
+        );
+        assert!(svc.call(request("/a/b")).into_inner().is_ok());
+        assert!(svc.call(request("/a/../b")).into_inner().is_err());
+
+        let mut svc = SanitizePath::with_config(
+            Counting::default(),
+            SanitizeConfig::new().response_header(
+                HeaderName::from_static("x-path-sanitized"),
+                HeaderValue::from_static("true"),
+            ),
+        );
+        assert!(svc.call(request("/a/b")).into_inner().is_ok());
+        assert!(svc.call(request("/a/../b")).into_inner().is_err());
+    }
+
+    #[tokio::test]
+    async fn rejections_never_call_inner() {
+        let configs = [
+            SanitizeConfig::new().reject_on_traversal(true),
+            SanitizeConfig::new().redirect(true),
+            SanitizeConfig::new().deny_paths(["/secret"]),
+            SanitizeConfig::new()
+                .rewrite_hook(|_, _| RewriteDecision::Reject(StatusCode::FORBIDDEN)),
+        ];
+
+        for config in configs {
+            let response = ServiceBuilder::new()
+                .layer(SanitizePathLayer::with_config(config))
+                .service_fn(
+                    |_: Request<()>| -> std::future::Ready<Result<Response<String>, Infallible>> {
+                        panic!("inner service called")
+                    },
+                )
+                .oneshot(Request::builder().uri("/a/../secret").body(()).unwrap())
+                .await
+                .unwrap();
+
+            assert_ne!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn skip_websocket() {
+        let config = SanitizeConfig::new().skip_websocket(true);
+        let svc = ServiceBuilder::new()
+            .layer(SanitizePathLayer::with_config(config.clone()))
+            .service_fn(|request: Request<()>| async move {
+                Ok::<_, Infallible>(Response::new(request.uri().to_string()))
+            });
+        let call = |request| svc.clone().oneshot(request);
+        let handshake = |uri| {
+            Request::builder()
+                .uri(uri)
+                .header(header::CONNECTION, "keep-alive, Upgrade")
+                .header(header::UPGRADE, "WebSocket")
+                .header(header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+        };
+
+        let upgrade = handshake("/socket/../a//b").body(()).unwrap();
+        assert_eq!(call(upgrade).await.unwrap().into_body(), "/socket/../a//b");
+
+        // Only a complete handshake skips the rewrite
+        let bare = Request::builder()
+            .uri("/socket/../a//b")
+            .header(header::UPGRADE, "websocket")
+            .body(())
+            .unwrap();
+        assert_eq!(call(bare).await.unwrap().into_body(), "/a/b");
+
+        let post = handshake("/socket/../a")
+            .method(http::Method::POST)
+            .body(())
+            .unwrap();
+        assert_eq!(call(post).await.unwrap().into_body(), "/a");
+
+        let other = handshake("/socket/../a//b")
+            .header(header::UPGRADE, "h2c")
+            .body(())
+            .unwrap();
+        assert_eq!(call(other).await.unwrap().into_body(), "/socket/../a//b");
+
+        let other = Request::builder()
+            .uri("/socket/../a//b")
+            .header(header::CONNECTION, "Upgrade")
+            .header(header::UPGRADE, "h2c")
+            .header(header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+            .body(())
+            .unwrap();
+        assert_eq!(call(other).await.unwrap().into_body(), "/a/b");
+
+        let plain = Request::builder().uri("/socket/../a").body(()).unwrap();
+        assert_eq!(call(plain).await.unwrap().into_body(), "/a");
+
+        // Rejections still apply to upgrade requests
+        let svc =
+            SanitizePathLayer::with_config(config.reject_on_traversal(true).deny_paths(["/admin"]))
+                .layer(tower::service_fn(|_: Request<()>| async {
+                    Ok::<_, Infallible>(Response::new(String::new()))
+                }));
+        let call = |request| svc.clone().oneshot(request);
+
+        let bare = Request::builder()
+            .uri("/a/../../etc/passwd")
+            .header(header::UPGRADE, "websocket")
+            .body(())
+            .unwrap();
+        assert_eq!(call(bare).await.unwrap().status(), StatusCode::BAD_REQUEST);
+
+        let upgrade = handshake("/a/../../etc/passwd").body(()).unwrap();
+        assert_eq!(
+            call(upgrade).await.unwrap().status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        let denied = handshake("/admin/socket").body(()).unwrap();
+        assert_eq!(call(denied).await.unwrap().status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn sanitize_paths_with_limits() {
+        let svc = SanitizePath::sanitize_paths_with_limits(
+            tower::service_fn(|request: Request<()>| async move {
+                Ok::<_, Infallible>(Response::new(request.uri().to_string()))
+            }),
+            16,
+            4,
+        );
+        let call = |uri: &str| {
+            svc.clone()
+                .oneshot(Request::builder().uri(uri).body(()).unwrap())
+        };
+
+        let response = call("/a/b/../c").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.into_body(), "/a/c");
+
+        let response = call("/aaaaaaaaaaaaaaaa").await.unwrap();
+        assert_eq!(response.status(), StatusCode::URI_TOO_LONG);
+
+        let response = call("/a/b/c/d/e").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Decoded separators count too
+        let response = call("/a/b%2fc%2fd%2fe").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn max_fs_path_len() {
+        const PATH_MAX: usize = 4096;
+
+        let svc = ServiceBuilder::new()
+            .layer(SanitizePathLayer::with_config(
+                SanitizeConfig::new()
+                    .max_fs_path_len(PATH_MAX)
+                    .document_root("/srv/www/"),
+            ))
+            .service_fn(|request: Request<()>| async move {
+                Ok::<_, Infallible>(Response::new(request.uri().to_string()))
+            });
+        let call = |uri: String| {
+            svc.clone()
+                .oneshot(Request::builder().uri(uri).body(()).unwrap())
+        };
+
+        // `/srv/www` takes 8 bytes of the limit
+        let fits = format!("/{}", "a".repeat(PATH_MAX - 9));
+        let response = call(fits.clone()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.into_body(), fits);
+
+        let response = call(format!("/{}", "a".repeat(PATH_MAX - 8)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Measured after sanitizing and decoding
+        let response = call(format!("/x/../{}", "a".repeat(PATH_MAX - 9)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = call(format!("/{}%7e", "a".repeat(PATH_MAX - 10)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = call(format!("/b/{}", "a".repeat(PATH_MAX - 10)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[tokio::test]
+    async fn deny_paths() {
+        let service = |config: SanitizeConfig| {
+            ServiceBuilder::new()
+                .layer(SanitizePathLayer::with_config(
+                    config.deny_paths(["/secret/"]),
+                ))
+                .service_fn(|request: Request<()>| async move {
+                    Ok::<_, Infallible>(Response::new(request.uri().to_string()))
+                })
+        };
+        let call = |config: SanitizeConfig, uri: &'static str| {
+            service(config).oneshot(Request::builder().uri(uri).body(()).unwrap())
+        };
+
+        let insensitive = || SanitizeConfig::new().deny_case_insensitive(true);
+        for uri in ["/Secret", "/SECRET/key", "/public/../secret", "/%53ecret"] {
+            let response = call(insensitive(), uri).await.unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{uri}");
+        }
+
+        // Allowed paths keep their case
+        let response = call(insensitive(), "/Public/../Secretive/File")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.into_body(), "/Secretive/File");
+
+        let response = call(SanitizeConfig::new(), "/secret/key").await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = call(SanitizeConfig::new(), "/Secret").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.into_body(), "/Secret");
+    }
+
+    #[tokio::test]
+    async fn deny_encoded_paths() {
+        for mode in [DecodeMode::TraversalOnly, DecodeMode::Literal] {
+            let config = SanitizeConfig::new()
+                .decode_mode(mode)
+                .deny_paths(["/admin"]);
+
+            for uri in ["/%61dmin", "/%61%64min/users", "/a/../%61dmin"] {
+                let response = call(config.clone(), uri).await;
+                assert_eq!(response.status(), StatusCode::FORBIDDEN, "{mode:?} {uri}");
+            }
+
+            // Only the comparison is decoded
+            let response = call(config, "/%61dministrator").await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.into_body(), "/%61dministrator None");
+        }
+    }
+
+    #[tokio::test]
+    async fn expected_hosts() {
+        let svc = ServiceBuilder::new()
+            .layer(SanitizePathLayer::with_config(
+                SanitizeConfig::new().expected_hosts(["example.com"]),
+            ))
+            .service_fn(|request: Request<()>| async move {
+                Ok::<_, Infallible>(Response::new(request.uri().to_string()))
+            });
+        let call = |uri: &str| {
+            svc.clone()
+                .oneshot(Request::builder().uri(uri).body(()).unwrap())
+        };
+
+        let response = call("http://example.com/a/../b").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.into_body(), "http://example.com/b");
+
+        let response = call("https://EXAMPLE.com:8443/a").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = call("/a/../b").await.unwrap();
+        assert_eq!(response.into_body(), "/b");
+
+        let response = call("http://other.example/a").await.unwrap();
+        assert_eq!(response.status(), StatusCode::MISDIRECTED_REQUEST);
+
+        let response = call("http://example.com.evil/a/../b").await.unwrap();
+        assert_eq!(response.status(), StatusCode::MISDIRECTED_REQUEST);
+
+        // Not checked by default
+        let response = ServiceBuilder::new()
+            .layer(SanitizePathLayer::new())
+            .service_fn(|_: Request<()>| async {
+                Ok::<_, Infallible>(Response::new(String::new()))
+            })
+            .oneshot(
+                Request::builder()
+                    .uri("http://other.example/a")
+                    .body(())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn around_runs_first() {
+        #[derive(Clone)]
+        struct SeenPath(String);
+
+        async fn handle(request: Request<()>) -> Result<Response<String>, Infallible> {
+            let SeenPath(seen) = request.extensions().get().unwrap();
+
+            Ok(Response::new(seen.clone()))
+        }
+
+        let record = || {
+            MapRequestLayer::new(|mut request: Request<()>| {
+                let path = request.uri().path().to_string();
+                request.extensions_mut().insert(SeenPath(path));
+                request
+            })
+        };
+        let request = || Request::builder().uri("/public/../admin").body(()).unwrap();
+
+        let body = ServiceBuilder::new()
+            .layer(SanitizePathLayer::new().around(record()))
+            .service_fn(handle)
+            .oneshot(request())
+            .await
+            .unwrap()
+            .into_body();
+        assert_eq!(body, "/admin");
+
+        // Added after the layer it should wrap
+        let body = ServiceBuilder::new()
+            .layer(record())
+            .layer(SanitizePathLayer::new())
+            .service_fn(handle)
+            .oneshot(request())
+            .await
+            .unwrap()
+            .into_body();
+        assert_eq!(body, "/public/../admin");
+    }
+
+    #[tokio::test]
+    async fn nested_layers_single_pass() {
+        async fn handle(request: Request<()>) -> Result<Response<String>, Infallible> {
+            let original = request.extensions().get::<OriginalPath>().unwrap();
+
+            Ok(Response::new(format!("{} {}", original.0, request.uri())))
+        }
+
+        let body = ServiceBuilder::new()
+            .layer(SanitizePathLayer::new())
+            .layer(SanitizePathLayer::new())
+            .service_fn(handle)
+            .oneshot(
+                Request::builder()
+                    .uri("/a/../%252e%252e/secret")
+                    .body(())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .into_body();
+
+        assert_eq!(body, "/a/../%252e%252e/secret /%252e%252e/secret");
+    }
+
+    #[tokio::test]
+    async fn nested_layers_path_changed_between() {
+        async fn handle(request: Request<()>) -> Result<Response<String>, Infallible> {
+            let original = request.extensions().get::<OriginalPath>().unwrap();
+
+            Ok(Response::new(format!("{} {}", original.0, request.uri())))
+        }
+
+        // Decodes `%25` once more, turning the sanitized `%252e` back into a dirty `%2e`
+        let decode = |mut request: Request<()>| {
+            let path = request.uri().path().replace("%25", "%");
+            *request.uri_mut() = path.parse().unwrap();
+            request
+        };
+
+        let body = ServiceBuilder::new()
+            .layer(SanitizePathLayer::new())
+            .map_request(decode)
+            .layer(SanitizePathLayer::new())
+            .service_fn(handle)
+            .oneshot(
+                Request::builder()
+                    .uri("/a/../%252e%252e/secret")
+                    .body(())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .into_body();
+
+        assert_eq!(body, "/a/../%252e%252e/secret /secret");
+    }
+
+    #[tokio::test]
+    async fn nested_layers_different_config() {
+        async fn handle(request: Request<()>) -> Result<Response<String>, Infallible> {
+            let original = request.extensions().get::<OriginalPath>().unwrap();
+
+            Ok(Response::new(format!("{} {}", original.0, request.uri())))
+        }
+
+        let body = ServiceBuilder::new()
+            .layer(SanitizePathLayer::new())
+            .layer(SanitizePathLayer::with_config(
+                SanitizeConfig::new().keep_trailing_slash(false),
+            ))
+            .service_fn(handle)
+            .oneshot(Request::builder().uri("/a/../b/").body(()).unwrap())
+            .await
+            .unwrap()
+            .into_body();
+
+        assert_eq!(body, "/a/../b/ /b");
+    }
+
+    #[tokio::test]
+    async fn hyper_service_fn() {
+        async fn handle(request: Request<hyper::Body>) -> Result<Response<String>, Infallible> {
+            Ok(Response::new(request.uri().to_string()))
+        }
+
+        let service = hyper::service::service_fn(sanitize_then(handle));
+        let request = Request::builder()
+            .uri("/a/../../secret?q=1")
+            .body(hyper::Body::empty())
+            .unwrap();
+
+        let body = service.oneshot(request).await.unwrap().into_body();
+        assert_eq!(body, "/secret?q=1");
+    }
+
+    #[tokio::test]
+    async fn retry_is_idempotent() {
+        use std::sync::Mutex;
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let inner = {
+            let seen = seen.clone();
+            tower::service_fn(move |request: Request<()>| {
+                seen.lock().unwrap().push(request);
+                async { Ok::<_, Infallible>(Response::new(String::new())) }
+            })
+        };
+
+        let config = SanitizeConfig::new().record_decoded_path(true);
+        let mut service = SanitizePath::with_config(inner, config);
+
+        let request = Request::builder()
+            .uri("/a/../%2e%2e/secret?q=1")
+            .body(())
+            .unwrap();
+        service.call(request).await.unwrap();
+
+        // Send the rewritten request again, as a retry of it would
+        let first = seen.lock().unwrap().pop().unwrap();
+        service.call(first).await.unwrap();
+
+        let second = seen.lock().unwrap().pop().unwrap();
+        assert_eq!(second.uri(), "/secret?q=1");
+        assert_eq!(
+            second.extensions().get(),
+            Some(&OriginalPath("/a/../%2e%2e/secret".to_string()))
+        );
+        assert_eq!(
+            second.extensions().get(),
+            Some(&DecodedPath("/a/../../secret".to_string()))
+        );
+
+        // A config which differs still leaves the first pass' extensions alone
+        let mut service = SanitizePath::with_config(
+            service.inner,
+            SanitizeConfig::new().keep_trailing_slash(false),
+        );
+        service.call(second).await.unwrap();
+
+        let third = seen.lock().unwrap().pop().unwrap();
+        assert_eq!(third.uri(), "/secret?q=1");
+        assert_eq!(
+            third.extensions().get(),
+            Some(&OriginalPath("/a/../%2e%2e/secret".to_string()))
+        );
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[tokio::test]
+    async fn record_outcome() {
+        use std::sync::Mutex;
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let inner = {
+            let seen = seen.clone();
+            tower::service_fn(move |request: Request<()>| {
+                seen.lock().unwrap().push(request);
+                async { Ok::<_, Infallible>(Response::new(String::new())) }
+            })
+        };
+
+        let config = SanitizeConfig::new().decode_rounds(2).record_outcome(true);
+        let mut service = SanitizePath::with_config(inner, config);
+
+        let request = Request::builder()
+            .uri("/a/%252e%252e/./b/../..%2fsecret?q=1")
+            .body(())
+            .unwrap();
+        service.call(request).await.unwrap();
+
+        let request = seen.lock().unwrap().pop().unwrap();
+        assert_eq!(request.uri(), "/secret?q=1");
+
+        let change = |segment: &str, class| SegmentChange {
+            segment: segment.to_string(),
+            class,
+        };
+        assert_eq!(
+            request.extensions().get(),
+            Some(&SanitizeOutcome {
+                class: SanitizeClass::ParentDir,
+                original: "/a/%252e%252e/./b/../..%2fsecret".to_string(),
+                removed: vec![
+                    change("a", SanitizeClass::ParentDir),
+                    change("..", SanitizeClass::ParentDir),
+                    change(".", SanitizeClass::CurrentDir),
+                    change("b", SanitizeClass::ParentDir),
+                    change("..", SanitizeClass::ParentDir),
+                    change("..", SanitizeClass::ParentDir),
+                ],
+                decode_rounds: 2,
+            })
+        );
+        assert!(request.extensions().get::<OriginalPath>().is_some());
+
+        // Clean paths get no outcome
+        let request = Request::builder().uri("/secret").body(()).unwrap();
+        service.call(request).await.unwrap();
+
+        let request = seen.lock().unwrap().pop().unwrap();
+        assert!(request.extensions().get::<SanitizeOutcome>().is_none());
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[tokio::test]
+    async fn reject_control_whitespace() {
+        let config = SanitizeConfig::new().reject_control_whitespace(true);
+
+        let response = call(config.clone(), "/safe%09/../secret").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = call(config, "/safe%20/../secret").await;
+        assert_eq!(response.into_body(), "/secret None");
+    }
+
+    #[test]
+    fn literal_decode_mode() {
+        let config = SanitizeConfig::new().decode_mode(DecodeMode::Literal);
+
+        let mut uri = "/%2e%2e/x".parse().unwrap();
+        assert_eq!(sanitize_path(&config, &mut uri), None);
+        assert_eq!(uri, "/%2e%2e/x");
+
+        let mut uri = "/a/../x".parse().unwrap();
+        sanitize_path(&config, &mut uri);
+        assert_eq!(uri, "/x");
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[test]
+    fn encoded_backslash_traversal() {
+        let config = SanitizeConfig::new().backslash_separator(true);
+
+        let mut uri = "/%2e%2e%5c%2e%2e%5csecret".parse().unwrap();
+        assert_eq!(
+            sanitize_path(&config, &mut uri),
+            Some(SanitizeClass::ParentDir)
+        );
+        assert_eq!(uri, "/secret");
+
+        // Without the option the backslashes are only decoded
+        let mut uri = "/%2e%2e%5c%2e%2e%5csecret".parse().unwrap();
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
+        assert_eq!(uri, "/..\\..\\secret");
+    }
+
+    #[test]
+    fn encoded_whitespace_traversal() {
+        let mut uri = "/a%20b/../c%20d?q=%20".parse().unwrap();
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
+
+        assert_eq!(uri, "/c%20d?q=%20");
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[test]
+    fn encoded_question_mark_stays_in_path() {
+        let mut uri: Uri = "/a%3f../b?real=1".parse().unwrap();
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
+
+        assert_eq!(uri, "/a%3F../b?real=1");
+        assert_eq!(uri.path(), "/a%3F../b");
+        assert_eq!(uri.query(), Some("real=1"));
+    }
+
+    #[test]
+    fn encoded_question_mark_with_query() {
+        let mut uri: Uri = "/%3Fa=b?c=d".parse().unwrap();
+        assert_eq!(sanitize_path(&SanitizeConfig::DEFAULT, &mut uri), None);
+        assert_eq!(uri, "/%3Fa=b?c=d");
+
+        let configs = [
+            SanitizeConfig::new(),
+            SanitizeConfig::new().decode_rounds(2),
+            SanitizeConfig::new().decode_mode(DecodeMode::TraversalOnly),
+            SanitizeConfig::new().decode_mode(DecodeMode::Literal),
+        ];
+
+        for config in configs {
+            for input in ["/%3Fa=b?c=d", "/x/../%3fa=b?c=d", "/x/../%253Fa=b?c=d"] {
+                let mut uri: Uri = input.parse().unwrap();
+                sanitize_path(&config, &mut uri);
+
+                assert!(!uri.path().contains('?'), "{input}");
+                assert_eq!(uri.query(), Some("c=d"), "{input}");
+                assert_eq!(uri.to_string().matches('?').count(), 1, "{input}");
+            }
+        }
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[test]
+    fn encoded_question_mark_without_query() {
+        let mut uri: Uri = "/x/%3f/../%3f".parse().unwrap();
+        sanitize_path(&SanitizeConfig::DEFAULT, &mut uri);
+
+        assert_eq!(uri.path(), "/x/%3F");
+        assert_eq!(uri.query(), None);
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[tokio::test]
+    async fn config_borrowed_per_request() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Deliberately not `Clone`, so the decoder can only ever be borrowed
+        struct Expensive {
+            calls: AtomicUsize,
+        }
+
+        let expensive = Arc::new(Expensive {
+            calls: AtomicUsize::new(0),
+        });
+        let decoder_state = expensive.clone();
+        let config = SanitizeConfig::new().decoder(move |path| {
+            decoder_state.calls.fetch_add(1, Ordering::Relaxed);
+            url_escape::decode(path)
+        });
+
+        let mut svc = SanitizePath::with_config(tower::service_fn(echo), config);
+
+        for uri in ["/a/../b", "/clean", "/%2e%2e/c"] {
+            let request = Request::builder().uri(uri).body(()).unwrap();
+            svc.ready().await.unwrap().call(request).await.unwrap();
+        }
+
+        assert_eq!(expensive.calls.load(Ordering::Relaxed), 3);
+        assert_eq!(Arc::strong_count(&expensive), 2);
+        assert_eq!(Arc::strong_count(&svc.config), 1);
+    }
+
+    #[tokio::test]
+    async fn empty_result() {
+        let config = SanitizeConfig::new().empty_result(EmptyResult::Root);
+        let response = call(config, "/a/../..").await;
+        assert_eq!(response.into_body(), "/ None");
+
+        let config = SanitizeConfig::new().empty_result(EmptyResult::Reject);
+        let response = call(config, "/a/../..").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let config = SanitizeConfig::new().empty_result(EmptyResult::Empty);
+        let mut uri = "/a/../..".parse().unwrap();
+        sanitize_path(&config, &mut uri);
+        assert_eq!(uri.path(), "");
+    }
+
+    #[test]
+    fn reconstruct_error() {
+        let uri: Uri = "/a/../b".parse().unwrap();
+        let normalized = Normalized {
+            // Not a valid path, as if normalization had a bug
+            path: "/b c".to_string(),
+            class: SanitizeClass::ParentDir,
+            decoded: Cow::Borrowed("/a/../b"),
+        };
+
+        assert!(matches!(
+            rebuild(&uri, normalized, None),
+            Err(SanitizeError::InvalidPath(_))
+        ));
+
+        let config = SanitizeConfig::new();
+        assert!(matches!(
+            reconstruct_failed(&config, SanitizeClass::ParentDir),
+            Ok(None)
+        ));
+
+        let config = SanitizeConfig::new().on_reconstruct_error(OnReconstructError::Reject(
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ));
+        assert_eq!(
+            reconstruct_failed(&config, SanitizeClass::ParentDir).err(),
+            Some(Rejected {
+                status: StatusCode::INTERNAL_SERVER_ERROR,
+                class: SanitizeClass::ParentDir,
+            })
+        );
+    }
+
+    #[test]
+    fn no_panics() {
+        // A small deterministic generator, so failures are reproducible
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+
+        let configs = [
+            SanitizeConfig::new(),
+            SanitizeConfig::new()
+                .decode_rounds(3)
+                .keep_trailing_slash(false)
+                .strip_matrix_params(true),
+            SanitizeConfig::new()
+                .decode_mode(DecodeMode::TraversalOnly)
+                .slash_mode(SlashMode::NginxMerge)
+                .empty_result(EmptyResult::Empty),
+            SanitizeConfig::new()
+                .reject_control_whitespace(true)
+                .empty_result(EmptyResult::Reject),
+        ];
+
+        for _ in 0..5_000 {
+            let mut path = String::from("/");
+            for _ in 0..next() % 24 {
+                match next() % 4 {
+                    // Any byte, percent-encoded to be lossily decoded
+                    0 => path.push_str(&format!("%{:02x}", next() as u8)),
+                    1 => path.push(['/', '.', '%', ';', '\\'][next() as usize % 5]),
+                    _ => path.push(char::from(b'!' + (next() % 94) as u8)),
+                }
+            }
+
+            let Ok(uri) = path.parse::<Uri>() else {
+                continue;
+            };
+
+            for config in &configs {
+                let _ = sanitized_uri(config, &uri);
+            }
+
+            let (once, _) = try_classify_and_sanitize(uri).unwrap();
+            let (twice, class) = try_classify_and_sanitize(once.clone()).unwrap();
+            assert_eq!(class, None, "{path} sanitized to {once} then {twice}");
+        }
+    }
+
+    #[tokio::test]
+    async fn skip_sanitize() {
+        let mut request = Request::builder().uri("/static/../raw").body(()).unwrap();
+        request.extensions_mut().insert(SkipSanitize);
+
+        let body = ServiceBuilder::new()
+            .layer(SanitizePathLayer::new())
+            .service_fn(echo)
+            .oneshot(request)
+            .await
+            .unwrap()
+            .into_body();
+
+        assert_eq!(body, "/static/../raw None");
+    }
 }
@@ -0,0 +1,1734 @@
+//! The path normalization used by [`SanitizePath`](crate::SanitizePath).
+
+use std::{borrow::Cow, fmt::Write};
+
+#[cfg(feature = "percent-decode")]
+use url_escape::decode;
+
+use crate::{
+    config::{
+        DecodeMode, EmptyResult, InvalidUtf8, OversizedSegment, PathStrategy, QueryControlPolicy,
+        RootBehavior, RootEscape, SlashMode,
+    },
+    report::{Changes, SegmentChange},
+    SanitizeConfig,
+};
+
+/// Why a path was changed by sanitization.
+///
+/// Variants are ordered from least to most severe. When several apply to the same path, the most
+/// severe one is reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum SanitizeClass {
+    /// Percent-decoding changed the path, but no segment was removed.
+    Decoded,
+    /// Percent-encoded control characters were stripped from the query, or rejected.
+    QueryControl,
+    /// Control characters were stripped from the path.
+    ControlChar,
+    /// Backslashes were replaced by `/` separators.
+    Backslash,
+    /// Matrix parameters (`;` and anything after it) were stripped from segments.
+    MatrixParam,
+    /// Empty segments, from repeated or unwanted trailing slashes, were removed.
+    EmptySegment,
+    /// Current directory (`.`) segments were removed.
+    CurrentDir,
+    /// The path was longer than the [`max_path_len`](SanitizeConfig::max_path_len), and it was
+    /// rejected.
+    PathTooLong,
+    /// The path had more segments than the [`max_segments`](SanitizeConfig::max_segments), and it
+    /// was rejected.
+    TooManySegments,
+    /// A segment longer than the [`max_segment_len`](SanitizeConfig::max_segment_len) was
+    /// truncated, or rejected.
+    OversizedSegment,
+    /// Parent directory (`..`) segments were resolved, removing them along with the segment each
+    /// one steps out of.
+    ParentDir,
+    /// The path resolved outside of the [`virtual_root`](SanitizeConfig::virtual_root), and was
+    /// clamped to it or rejected.
+    RootEscape,
+    /// A segment contained control whitespace, like a tab or newline, and was rejected.
+    ControlWhitespace,
+    /// Decoding turned part of a segment into a separator, and the path was rejected.
+    EncodedSeparator,
+    /// Percent-encoded bytes did not decode to valid UTF-8, and were rejected.
+    InvalidUtf8,
+    /// A custom decoder grew the path beyond the
+    /// [`max_decode_ratio`](SanitizeConfig::max_decode_ratio), and it was rejected.
+    DecodeAmplification,
+    /// The sanitized path was one of the [`deny_paths`](SanitizeConfig::deny_paths), and it was
+    /// rejected.
+    Denied,
+    /// The filesystem path of the sanitized path was longer than the
+    /// [`max_fs_path_len`](SanitizeConfig::max_fs_path_len), and it was rejected.
+    FsPathTooLong,
+    /// The authority of an absolute-form request target was not one of the
+    /// [`expected_hosts`](SanitizeConfig::expected_hosts), and it was rejected.
+    MisdirectedAuthority,
+}
+
+impl SanitizeClass {
+    /// A short human readable description of the class.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Decoded => "percent-encoded path decoded",
+            Self::QueryControl => "control character in query",
+            Self::ControlChar => "control character removed from path",
+            Self::Backslash => "backslash separator replaced",
+            Self::MatrixParam => "matrix parameter removed",
+            Self::EmptySegment => "empty path segment removed",
+            Self::CurrentDir => "current directory segment removed",
+            Self::PathTooLong => "path too long",
+            Self::TooManySegments => "too many path segments",
+            Self::OversizedSegment => "path segment too long",
+            Self::ParentDir => "path traversal detected",
+            Self::RootEscape => "path outside of the virtual root",
+            Self::ControlWhitespace => "control whitespace in path",
+            Self::EncodedSeparator => "encoded separator in path",
+            Self::InvalidUtf8 => "invalid UTF-8 in path",
+            Self::DecodeAmplification => "decoded path too large",
+            Self::Denied => "path denied",
+            Self::FsPathTooLong => "filesystem path too long",
+            Self::MisdirectedAuthority => "request target for another host",
+        }
+    }
+}
+
+/// A path changed by [`normalize`].
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Normalized<'a> {
+    /// The sanitized path.
+    pub(crate) path: String,
+    /// Why the path changed.
+    pub(crate) class: SanitizeClass,
+    /// The decoded path, before any segments were changed.
+    pub(crate) decoded: Cow<'a, str>,
+}
+
+const CURRENT_DIR: &str = ".";
+const PARENT_DIR: &str = "..";
+
+/// The dot-segments resolved away from every path, along with the segment before each `..`.
+pub const DANGEROUS_SEGMENTS: &[&str] = &[CURRENT_DIR, PARENT_DIR];
+
+/// Whitespace some backends treat as a path terminator, rejected by
+/// [`reject_control_whitespace`](SanitizeConfig::reject_control_whitespace).
+pub const CONTROL_WHITESPACE: &[char] = &['\t', '\n', '\u{b}', '\u{c}', '\r'];
+
+/// The control characters `0x00` to `0x1f` and `0x7f`, stripped from paths by
+/// [`strip_control_chars`](SanitizeConfig::strip_control_chars) and handled by
+/// [`query_control_policy`](SanitizeConfig::query_control_policy) when percent-encoded in the
+/// query.
+pub const CONTROL_CHARS: [u8; 33] = {
+    let mut chars = [0x7f; 33];
+    let mut i = 0;
+    while i < 0x20 {
+        chars[i] = i as u8;
+        i += 1;
+    }
+    chars
+};
+
+/// Where a path being normalized comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PathSource {
+    /// [`http::Uri::path`], which only holds bytes accepted by [`is_path_byte`].
+    Uri,
+    /// Any other string.
+    Str,
+}
+
+/// Normalize `path`, returning `None` if it is already clean or the reason it should be rejected.
+#[cfg(test)]
+pub(crate) fn normalize<'a>(
+    config: &SanitizeConfig,
+    path: &'a str,
+) -> Result<Option<Normalized<'a>>, SanitizeClass> {
+    normalize_with(config, path, PathSource::Str, None)
+}
+
+/// [`normalize`] for a path from `source`, recording every segment removed or modified and
+/// every decode round into `changes` if given.
+pub(crate) fn normalize_with<'a>(
+    config: &SanitizeConfig,
+    path: &'a str,
+    source: PathSource,
+    changes: Option<&mut Changes>,
+) -> Result<Option<Normalized<'a>>, SanitizeClass> {
+    let mut new_path = String::new();
+    let normalized = normalize_into(config, path, source, changes, &mut new_path)?;
+
+    Ok(normalized.map(|(class, decoded)| Normalized {
+        path: new_path,
+        class,
+        decoded,
+    }))
+}
+
+/// [`normalize_with`] using the default config, `None` if `path` is already clean.
+pub(crate) fn normalize_default<'a>(
+    path: &'a str,
+    source: PathSource,
+    changes: Option<&mut Changes>,
+) -> Option<Normalized<'a>> {
+    let mut new_path = String::new();
+    let normalized = normalize_default_into(path, source, changes, &mut new_path);
+
+    normalized.map(|(class, decoded)| Normalized {
+        path: new_path,
+        class,
+        decoded,
+    })
+}
+
+/// [`normalize_into`] using the default config, `None` if `path` is already clean.
+pub(crate) fn normalize_default_into<'a>(
+    path: &'a str,
+    source: PathSource,
+    changes: Option<&mut Changes>,
+    out: &mut String,
+) -> Option<(SanitizeClass, Cow<'a, str>)> {
+    // Every rejection comes from an option the default config leaves disabled, so the helpers
+    // built on it have no error to report
+    normalize_into(&SanitizeConfig::DEFAULT, path, source, changes, out)
+        .ok()
+        .flatten()
+}
+
+/// [`normalize_with`], writing the sanitized path into `out` instead of allocating it.
+///
+/// `out` is cleared first, and only holds the sanitized path if the path changed.
+pub(crate) fn normalize_into<'a>(
+    config: &SanitizeConfig,
+    path: &'a str,
+    source: PathSource,
+    changes: Option<&mut Changes>,
+    out: &mut String,
+) -> Result<Option<(SanitizeClass, Cow<'a, str>)>, SanitizeClass> {
+    out.clear();
+
+    if let Some(max) = config.max_path_len {
+        if path.len() > max {
+            return Err(SanitizeClass::PathTooLong);
+        }
+    }
+    if let Some(max) = config.max_segments {
+        if path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .count()
+            > max
+        {
+            return Err(SanitizeClass::TooManySegments);
+        }
+    }
+
+    let normalized = match config.path_strategy {
+        PathStrategy::Whatwg => {
+            normalize_whatwg(path, out).map(|class| (class, Cow::Borrowed(path)))
+        }
+        PathStrategy::GoClean => normalize_go(path, out).map(|class| (class, Cow::Borrowed(path))),
+        PathStrategy::Segments => normalize_segments(config, path, source, changes, out)?,
+    };
+
+    let Some(root) = &config.virtual_root else {
+        return Ok(normalized);
+    };
+
+    let sanitized = if normalized.is_some() { &**out } else { path };
+    let under_root = match sanitized.strip_prefix(root.as_str()) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/') || root == "/",
+        None => false,
+    };
+    if under_root {
+        return Ok(normalized);
+    }
+
+    match config.root_escape {
+        // Clamping would hide the traversal, which ranks below escaping the root
+        RootEscape::Clamp
+            if config.reject_on_traversal
+                && matches!(normalized, Some((SanitizeClass::ParentDir, _))) =>
+        {
+            Err(SanitizeClass::ParentDir)
+        }
+        RootEscape::Clamp => {
+            out.clear();
+            out.push_str(root);
+            let decoded = normalized.map_or(Cow::Borrowed(path), |(_, decoded)| decoded);
+
+            Ok(Some((SanitizeClass::RootEscape, decoded)))
+        }
+        RootEscape::Reject => Err(SanitizeClass::RootEscape),
+    }
+}
+
+/// [`normalize_into`] for [`PathStrategy::Segments`], ignoring the virtual root.
+fn normalize_segments<'a>(
+    config: &SanitizeConfig,
+    path: &'a str,
+    source: PathSource,
+    mut changes: Option<&mut Changes>,
+    out: &mut String,
+) -> Result<Option<(SanitizeClass, Cow<'a, str>)>, SanitizeClass> {
+    if is_clean(config, path, source) {
+        return Ok(None);
+    }
+
+    let mut decoded = Cow::Borrowed(path);
+    for _ in 0..config.decode_rounds {
+        let next = match config.decode_mode {
+            #[cfg(feature = "percent-decode")]
+            DecodeMode::Full => match &config.decoder {
+                Some(decoder) => decoder.decode(&decoded),
+                None if config.invalid_utf8 == InvalidUtf8::Reject => decode_strict(&decoded)?,
+                None => decode(&decoded),
+            },
+            DecodeMode::TraversalOnly => decode_traversal(&decoded),
+            DecodeMode::Literal => break,
+        };
+
+        match next {
+            Cow::Owned(next) => decoded = Cow::Owned(next),
+            Cow::Borrowed(_) => break,
+        }
+        if let Some(changes) = changes.as_mut() {
+            changes.decode_rounds += 1;
+        }
+
+        if let Some(ratio) = config.max_decode_ratio {
+            if decoded.len() > path.len().saturating_mul(ratio.into()) {
+                return Err(SanitizeClass::DecodeAmplification);
+            }
+        }
+    }
+
+    if config.noncharacters_invalid && decoded.contains(is_noncharacter) {
+        decoded = match config.invalid_utf8 {
+            InvalidUtf8::Replace => Cow::Owned(decoded.replace(is_noncharacter, "\u{fffd}")),
+            InvalidUtf8::Reject => return Err(SanitizeClass::InvalidUtf8),
+        };
+    }
+
+    if config.reject_control_whitespace && decoded.contains(CONTROL_WHITESPACE) {
+        return Err(SanitizeClass::ControlWhitespace);
+    }
+
+    let mut class = None;
+    let mut note = |c: SanitizeClass| class = class.max(Some(c));
+
+    // Dropped before splitting, so `..%00` is seen as the `..` some backends make of it
+    let is_control = |c: char| c.is_ascii() && CONTROL_CHARS.contains(&(c as u8));
+    if config.strip_control_chars && decoded.contains(is_control) {
+        decoded = Cow::Owned(decoded.replace(is_control, ""));
+        note(SanitizeClass::ControlChar);
+    }
+
+    // Comparing the rebuilt path with the original catches the change, no class is needed
+    #[cfg(feature = "unicode")]
+    if config.unicode_nfc {
+        use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+        if !is_nfc(&decoded) {
+            decoded = Cow::Owned(decoded.nfc().collect());
+        }
+    }
+
+    let mut separated = Cow::Borrowed(&*decoded);
+    if config.backslash_separator && decoded.contains('\\') {
+        separated = Cow::Owned(decoded.replace('\\', "/"));
+        note(SanitizeClass::Backslash);
+    }
+
+    // Raw backslashes are separators too, so only decoded ones change the structure
+    if config.reject_on_encoded_separator {
+        let separators = |path: &str| {
+            path.bytes()
+                .filter(|&b| b == b'/' || (config.backslash_separator && b == b'\\'))
+                .count()
+        };
+        if separators(&separated) != separators(path) {
+            return Err(SanitizeClass::EncodedSeparator);
+        }
+    }
+
+    let (rooted, rest) = match separated.strip_prefix('/') {
+        Some(rest) => (true, rest),
+        None => (false, &*separated),
+    };
+    let rooted = match config.root_behavior {
+        RootBehavior::PreserveInput => rooted,
+        RootBehavior::ForceRoot => rooted || !(path.is_empty() || path == "*"),
+        RootBehavior::Strip => false,
+    };
+
+    let keep_trailing_slash =
+        config.keep_trailing_slash || config.slash_mode == SlashMode::NginxMerge;
+
+    let mut segments = Vec::new();
+    let mut trailing_slash = false;
+    let mut iter = rest.split('/').peekable();
+
+    let mut record = |segment: &str, class: SanitizeClass| {
+        if let Some(changes) = changes.as_mut() {
+            changes.segments.push(SegmentChange {
+                segment: segment.to_owned(),
+                class,
+            });
+        }
+    };
+
+    while let Some(mut segment) = iter.next() {
+        let last = iter.peek().is_none();
+
+        // Servlet containers drop these, so `..;` has to be seen as `..`
+        if config.strip_matrix_params {
+            if let Some((name, _)) = segment.split_once(';') {
+                record(segment, SanitizeClass::MatrixParam);
+                segment = name;
+                note(SanitizeClass::MatrixParam);
+            }
+        }
+
+        // Truncated before looking for dot-segments, so cutting `..x` down to `..` still
+        // resolves it
+        if let Some(max) = config.max_segment_len {
+            if segment.len() > max {
+                match config.oversized_segment {
+                    OversizedSegment::Reject => return Err(SanitizeClass::OversizedSegment),
+                    OversizedSegment::Truncate => {
+                        let mut end = max;
+                        while !segment.is_char_boundary(end) {
+                            end -= 1;
+                        }
+                        record(segment, SanitizeClass::OversizedSegment);
+                        segment = &segment[..end];
+                        note(SanitizeClass::OversizedSegment);
+                    }
+                }
+            }
+        }
+
+        #[cfg(feature = "unicode")]
+        if config.dot_homoglyphs {
+            if let Some(dots) = fold_dot_homoglyphs(segment) {
+                segment = dots;
+            }
+        }
+
+        if config.aggressive_dots && segment.len() > 2 && segment.bytes().all(|b| b == b'.') {
+            segment = "..";
+        }
+
+        match segment {
+            "" if last => {
+                if rest.is_empty() {
+                    // The root path itself
+                } else if keep_trailing_slash {
+                    trailing_slash = true;
+                } else {
+                    record(segment, SanitizeClass::EmptySegment);
+                    note(SanitizeClass::EmptySegment);
+                }
+            }
+            "" => {
+                record(segment, SanitizeClass::EmptySegment);
+                note(SanitizeClass::EmptySegment);
+            }
+            CURRENT_DIR => {
+                record(segment, SanitizeClass::CurrentDir);
+                note(SanitizeClass::CurrentDir);
+            }
+            PARENT_DIR => {
+                // Traversing above the root simply stays at the root
+                if let Some(parent) = segments.pop() {
+                    record(parent, SanitizeClass::ParentDir);
+                }
+                record(segment, SanitizeClass::ParentDir);
+                note(SanitizeClass::ParentDir);
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    // Decoded separators may have added segments
+    if let Some(max) = config.max_segments {
+        if segments
+            .iter()
+            .filter(|segment| !segment.is_empty())
+            .count()
+            > max
+        {
+            return Err(SanitizeClass::TooManySegments);
+        }
+    }
+
+    // Something other than the root itself resolved down to nothing, like `/a/..`
+    let resolved_away = rooted && segments.is_empty() && path != "/";
+    let mut empty = false;
+    if resolved_away {
+        match config.empty_result {
+            EmptyResult::Root => {}
+            EmptyResult::Reject => return Err(class.unwrap_or(SanitizeClass::Decoded)),
+            EmptyResult::Empty => empty = true,
+        }
+    }
+
+    // Decoded `%` must stay encoded so the path is not decoded again downstream
+    let encode_percent = config.decode_mode.decodes_all();
+
+    let new_path = out;
+    new_path.reserve(decoded.len());
+    for (i, segment) in segments.iter().enumerate() {
+        if rooted || i > 0 {
+            new_path.push('/');
+        }
+        encode_segment(segment, encode_percent, new_path);
+    }
+    if rooted && segments.is_empty() && !empty {
+        new_path.push('/');
+    }
+
+    // A trailing slash on the root would make it `//`
+    if trailing_slash && !segments.is_empty() {
+        new_path.push('/');
+    }
+
+    if path == *new_path {
+        return Ok(None);
+    }
+
+    Ok(Some((class.unwrap_or(SanitizeClass::Decoded), decoded)))
+}
+
+/// The `.` or `..` spelled by `segment` if it is made of dots with at least one homoglyph of `.`.
+#[cfg(feature = "unicode")]
+fn fold_dot_homoglyphs(segment: &str) -> Option<&'static str> {
+    // Fullwidth, ideographic, small and halfwidth ideographic full stops
+    const HOMOGLYPHS: [char; 4] = ['\u{FF0E}', '\u{3002}', '\u{FE52}', '\u{FF61}'];
+
+    if segment.is_ascii() {
+        return None;
+    }
+
+    let mut dots = 0;
+    for c in segment.chars() {
+        if c != '.' && !HOMOGLYPHS.contains(&c) {
+            return None;
+        }
+        dots += 1;
+    }
+
+    match dots {
+        1 => Some("."),
+        2 => Some(".."),
+        _ => None,
+    }
+}
+
+/// Normalize `path` following the path state of the WHATWG URL standard for special schemes.
+///
+/// The new path is written into `new_path`, returning why it changed.
+fn normalize_whatwg(path: &str, new_path: &mut String) -> Option<SanitizeClass> {
+    // Not paths which can have segments
+    if path.is_empty() || path == "*" {
+        return None;
+    }
+
+    let is_separator = |c| c == '/' || c == '\\';
+    let (rooted, rest) = match path.strip_prefix(is_separator) {
+        Some(rest) => (true, rest),
+        None => (false, path),
+    };
+
+    let mut class = None;
+    let mut note = |c: SanitizeClass| class = class.max(Some(c));
+
+    if path.contains('\\') {
+        note(SanitizeClass::Backslash);
+    }
+
+    let mut segments = Vec::new();
+    let mut iter = rest.split(is_separator).peekable();
+
+    while let Some(segment) = iter.next() {
+        let last = iter.peek().is_none();
+
+        let is_any = |forms: &[&str]| forms.iter().any(|form| segment.eq_ignore_ascii_case(form));
+        let dots = if is_any(&[".", "%2e"]) {
+            1
+        } else if is_any(&["..", ".%2e", "%2e.", "%2e%2e"]) {
+            2
+        } else {
+            0
+        };
+
+        match dots {
+            0 => segments.push(segment),
+            _ => {
+                if dots == 2 {
+                    segments.pop();
+                    note(SanitizeClass::ParentDir);
+                } else {
+                    note(SanitizeClass::CurrentDir);
+                }
+
+                // A trailing dot-segment still ends the path in a slash
+                if last {
+                    segments.push("");
+                }
+            }
+        }
+    }
+
+    new_path.reserve(path.len());
+    for (i, segment) in segments.iter().enumerate() {
+        if rooted || i > 0 {
+            new_path.push('/');
+        }
+        for &byte in segment.as_bytes() {
+            // The path percent-encode set
+            let encode = matches!(
+                byte,
+                0..=0x20 | b'"' | b'#' | b'<' | b'>' | b'?' | b'`' | b'{' | b'}' | 0x7f..=0xff
+            );
+
+            if encode {
+                let _ = write!(new_path, "%{byte:02X}");
+            } else {
+                new_path.push(byte as char);
+            }
+        }
+    }
+
+    if path == *new_path {
+        return None;
+    }
+
+    Some(class.unwrap_or(SanitizeClass::Decoded))
+}
+
+/// Normalize `path` following Go's `path.Clean`, except for leaving empty paths alone.
+///
+/// The new path is written into `new_path`, returning why it changed.
+fn normalize_go(path: &str, new_path: &mut String) -> Option<SanitizeClass> {
+    // Authority-form targets, which Go would make `.`
+    if path.is_empty() {
+        return None;
+    }
+
+    let (rooted, rest) = match path.strip_prefix('/') {
+        Some(rest) => (true, rest),
+        None => (false, path),
+    };
+
+    let mut class = None;
+    let mut note = |c: SanitizeClass| class = class.max(Some(c));
+
+    let mut segments = Vec::new();
+    // The leading `..` segments of a relative path, which cannot be resolved
+    let mut unresolved = 0;
+
+    for segment in rest.split('/') {
+        match segment {
+            "" => note(SanitizeClass::EmptySegment),
+            "." => note(SanitizeClass::CurrentDir),
+            ".." if segments.len() > unresolved => {
+                segments.pop();
+                note(SanitizeClass::ParentDir);
+            }
+            ".." if rooted => note(SanitizeClass::ParentDir),
+            ".." => {
+                segments.push(segment);
+                unresolved += 1;
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    new_path.reserve(path.len());
+    if rooted {
+        new_path.push('/');
+    }
+    for (i, segment) in segments.iter().enumerate() {
+        if i > 0 {
+            new_path.push('/');
+        }
+        new_path.push_str(segment);
+    }
+    if new_path.is_empty() {
+        new_path.push('.');
+    }
+
+    if path == *new_path {
+        return None;
+    }
+
+    Some(class.unwrap_or(SanitizeClass::Decoded))
+}
+
+/// Push `segment` onto `out`, percent-encoding every byte which is not valid in a URI path.
+///
+/// Decoded whitespace, `?` and `#` are encoded again so they cannot end the path early.
+fn encode_segment(segment: &str, encode_percent: bool, out: &mut String) {
+    for &byte in segment.as_bytes() {
+        if is_path_byte(byte) && !(encode_percent && byte == b'%') {
+            out.push(byte as char);
+        } else {
+            let _ = write!(out, "%{byte:02X}");
+        }
+    }
+}
+
+/// Whether `byte` is one of the path bytes accepted by `http::Uri`.
+fn is_path_byte(byte: u8) -> bool {
+    matches!(
+        byte,
+        b'!' | b'$'..=b';' | b'=' | b'@'..=b'_' | b'a'..=b'z' | b'|' | b'~' | b'"' | b'{' | b'}'
+    )
+}
+
+/// Whether [`normalize`] would leave `path` untouched, checked in a single pass over its bytes.
+///
+/// This is the hot case of most requests, so it skips decoding and splitting segments entirely.
+/// It may return `false` for some clean paths, which then take the slow path. Any option that
+/// can change a path without `%`, `//` or dot-segments has to be checked here.
+fn is_clean(config: &SanitizeConfig, path: &str, source: PathSource) -> bool {
+    // A custom decoder may decode more than `%`
+    #[cfg(feature = "percent-decode")]
+    if config.decoder.is_some() {
+        return false;
+    }
+
+    let rooted = path.starts_with('/');
+    let root_changes = match config.root_behavior {
+        RootBehavior::PreserveInput => false,
+        RootBehavior::ForceRoot => !rooted,
+        RootBehavior::Strip => rooted,
+    };
+    if root_changes {
+        return false;
+    }
+
+    let rest = path.strip_prefix('/').unwrap_or(path).as_bytes();
+    if !scan_clean(config, rest, source) {
+        return false;
+    }
+
+    let keep_trailing_slash =
+        config.keep_trailing_slash || config.slash_mode == SlashMode::NginxMerge;
+
+    keep_trailing_slash || rest.last() != Some(&b'/') || path == "/"
+}
+
+/// Whether none of the bytes of `rest`, the path after its leading `/`, make it dirty.
+#[cfg_attr(not(feature = "fast-scan"), allow(unused_variables))]
+fn scan_clean(config: &SanitizeConfig, rest: &[u8], source: PathSource) -> bool {
+    #[cfg(feature = "fast-scan")]
+    if source == PathSource::Uri
+        && config.max_segment_len.is_none()
+        && config.decode_mode != DecodeMode::Literal
+        && !config.strip_matrix_params
+        && !config.backslash_separator
+    {
+        return scan_clean_fast(rest);
+    }
+
+    let max_segment_len = config.max_segment_len.unwrap_or(usize::MAX);
+    let mut segment_len = 0;
+
+    // Start as if after a `/` so a leading dot-segment or empty segment is caught as well
+    let mut prev = b'/';
+    for &byte in rest {
+        segment_len = if byte == b'/' { 0 } else { segment_len + 1 };
+
+        let dirty = segment_len > max_segment_len
+            || match byte {
+                b'%' => config.decode_mode != DecodeMode::Literal,
+                b'/' | b'.' => prev == b'/',
+                b';' => config.strip_matrix_params,
+                b'\\' => config.backslash_separator,
+                byte => !is_path_byte(byte),
+            };
+        if dirty {
+            return false;
+        }
+        prev = byte;
+    }
+
+    true
+}
+
+/// [`scan_clean`] for URI paths under configs where only `%`, `//` and dot-segments make a path
+/// dirty, jumping between `%` and `/` with the SIMD search of `memchr`.
+///
+/// `http::Uri` already rejected every byte [`is_path_byte`] does, so the rest can be skipped.
+#[cfg(feature = "fast-scan")]
+fn scan_clean_fast(rest: &[u8]) -> bool {
+    // The bytes after a `/`, including the one the path started with
+    let after_slash = |i: usize| matches!(rest.get(i), Some(b'/' | b'.'));
+
+    !after_slash(0)
+        && memchr::memchr2_iter(b'%', b'/', rest).all(|i| rest[i] == b'/' && !after_slash(i + 1))
+}
+
+/// Apply `policy` to the percent-encoded control characters in `query`, returning the new query
+/// if any were stripped.
+pub(crate) fn clean_query(
+    policy: QueryControlPolicy,
+    query: &str,
+) -> Result<Option<String>, SanitizeClass> {
+    let hex = |digit: u8| (digit as char).to_digit(16);
+    let is_control = |encoded: &[u8]| match encoded {
+        [b'%', high, low] => match (hex(*high), hex(*low)) {
+            (Some(high), Some(low)) => CONTROL_CHARS.contains(&(high as u8 * 16 + low as u8)),
+            _ => false,
+        },
+        _ => false,
+    };
+
+    let bytes = query.as_bytes();
+    let mut controls = (0..bytes.len())
+        .filter(|&i| is_control(&bytes[i..bytes.len().min(i + 3)]))
+        .peekable();
+
+    if controls.peek().is_none() {
+        return Ok(None);
+    }
+
+    match policy {
+        QueryControlPolicy::Keep => Ok(None),
+        QueryControlPolicy::Reject => Err(SanitizeClass::QueryControl),
+        QueryControlPolicy::Strip => {
+            let mut stripped = String::with_capacity(query.len());
+            let mut start = 0;
+            // Matches never overlap, as `%` is not a hex digit
+            for i in controls {
+                stripped.push_str(&query[start..i]);
+                start = i + 3;
+            }
+            stripped.push_str(&query[start..]);
+
+            Ok(Some(stripped))
+        }
+    }
+}
+
+/// Whether `c` is one of the 66 Unicode noncharacters.
+fn is_noncharacter(c: char) -> bool {
+    matches!(c, '\u{fdd0}'..='\u{fdef}') || c as u32 & 0xfffe == 0xfffe
+}
+
+/// Percent-decode `path`, failing if the decoded bytes are not valid UTF-8.
+#[cfg(feature = "percent-decode")]
+fn decode_strict(path: &str) -> Result<Cow<'_, str>, SanitizeClass> {
+    if !path.contains('%') {
+        return Ok(Cow::Borrowed(path));
+    }
+
+    let mut bytes = Vec::new();
+    url_escape::decode_to_vec(path, &mut bytes);
+    if bytes == path.as_bytes() {
+        return Ok(Cow::Borrowed(path));
+    }
+
+    String::from_utf8(bytes)
+        .map(Cow::Owned)
+        .map_err(|_| SanitizeClass::InvalidUtf8)
+}
+
+/// Decode only `%2e` and `%2f`, in either case.
+fn decode_traversal(path: &str) -> Cow<'_, str> {
+    if !path.contains('%') {
+        return Cow::Borrowed(path);
+    }
+
+    let mut decoded = String::with_capacity(path.len());
+    let mut rest = path;
+
+    while let Some(index) = rest.find('%') {
+        decoded.push_str(&rest[..index]);
+        rest = &rest[index..];
+
+        let replacement = match rest.get(1..3) {
+            Some(hex) if hex.eq_ignore_ascii_case("2e") => Some('.'),
+            Some(hex) if hex.eq_ignore_ascii_case("2f") => Some('/'),
+            _ => None,
+        };
+
+        if let Some(replacement) = replacement {
+            decoded.push(replacement);
+            rest = &rest[3..];
+        } else {
+            decoded.push('%');
+            rest = &rest[1..];
+        }
+    }
+    decoded.push_str(rest);
+
+    if decoded == path {
+        Cow::Borrowed(path)
+    } else {
+        Cow::Owned(decoded)
+    }
+}
+
+/// Decode the percent-encoded unreserved characters of `path`, letters, digits, `-`, `.`, `_`
+/// and `~`, which mean the same encoded or not.
+pub(crate) fn decode_unreserved(path: &str) -> Cow<'_, str> {
+    if !path.contains('%') {
+        return Cow::Borrowed(path);
+    }
+
+    let mut decoded = String::with_capacity(path.len());
+    let mut rest = path;
+
+    while let Some(index) = rest.find('%') {
+        decoded.push_str(&rest[..index]);
+        rest = &rest[index..];
+
+        let replacement = rest
+            .get(1..3)
+            .filter(|hex| hex.bytes().all(|digit| digit.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .filter(|&byte| byte.is_ascii_alphanumeric() || b"-._~".contains(&byte));
+
+        if let Some(replacement) = replacement {
+            decoded.push(char::from(replacement));
+            rest = &rest[3..];
+        } else {
+            decoded.push('%');
+            rest = &rest[1..];
+        }
+    }
+    decoded.push_str(rest);
+
+    if decoded == path {
+        Cow::Borrowed(path)
+    } else {
+        Cow::Owned(decoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(config: &SanitizeConfig, path: &str) -> Option<(String, SanitizeClass)> {
+        super::normalize(config, path)
+            .unwrap()
+            .map(|normalized| (normalized.path, normalized.class))
+    }
+
+    #[test]
+    fn decode_unreserved_only() {
+        assert_eq!(decode_unreserved("/%61dmin/%7E%2D"), "/admin/~-");
+        assert_eq!(decode_unreserved("/a%2fb%20c%25"), "/a%2fb%20c%25");
+        assert_eq!(decode_unreserved("/%+1%6"), "/%+1%6");
+        assert!(matches!(decode_unreserved("/%2F"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn decode_traversal_only() {
+        assert_eq!(decode_traversal("/%41/%2e%2E/x%2F%2"), "/%41/../x/%2");
+        assert!(matches!(decode_traversal("/%41/x"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn traversal_only_keeps_other_encodings() {
+        let config = SanitizeConfig::new().decode_mode(DecodeMode::TraversalOnly);
+        let (path, class) = normalize(&config, "/%41/%2e%2e/x").unwrap();
+
+        assert_eq!(path, "/x");
+        assert_eq!(class, SanitizeClass::ParentDir);
+
+        let (path, _) = normalize(&config, "/%41/b/%2e%2e/x").unwrap();
+        assert_eq!(path, "/%41/x");
+
+        assert_eq!(normalize(&config, "/%41/x"), None);
+    }
+
+    #[test]
+    fn literal_decodes_nothing() {
+        let config = SanitizeConfig::new().decode_mode(DecodeMode::Literal);
+
+        assert_eq!(normalize(&config, "/%2e%2e/x"), None);
+        assert_eq!(normalize(&config, "/%252e%252e/x"), None);
+        assert_eq!(
+            normalize(&config, "/%41/../%2e%2e/x"),
+            Some(("/%2e%2e/x".to_string(), SanitizeClass::ParentDir))
+        );
+    }
+
+    #[test]
+    fn nginx_merge_slashes() {
+        let config = SanitizeConfig::new()
+            .keep_trailing_slash(false)
+            .slash_mode(SlashMode::NginxMerge);
+
+        assert_eq!(
+            normalize(&config, "//"),
+            Some(("/".to_string(), SanitizeClass::EmptySegment))
+        );
+        assert_eq!(
+            normalize(&config, "//a//b"),
+            Some(("/a/b".to_string(), SanitizeClass::EmptySegment))
+        );
+        assert_eq!(
+            normalize(&config, "/a//b//"),
+            Some(("/a/b/".to_string(), SanitizeClass::EmptySegment))
+        );
+        assert_eq!(normalize(&config, "/a/b/"), None);
+    }
+
+    #[test]
+    fn collapse_slashes() {
+        let config = SanitizeConfig::new().keep_trailing_slash(false);
+
+        assert_eq!(
+            normalize(&config, "/a//b//"),
+            Some(("/a/b".to_string(), SanitizeClass::EmptySegment))
+        );
+    }
+
+    #[test]
+    fn keep_decoded() {
+        let normalized = super::normalize(&SanitizeConfig::DEFAULT, "/a/%2e%2E/b")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(normalized.decoded, "/a/../b");
+    }
+
+    #[test]
+    fn encoded_whitespace_traversal() {
+        assert_eq!(
+            normalize(&SanitizeConfig::DEFAULT, "/safe%20/../secret"),
+            Some(("/secret".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(
+            normalize(&SanitizeConfig::DEFAULT, "/a%20b/c%09d/../e"),
+            Some(("/a%20b/e".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(normalize(&SanitizeConfig::DEFAULT, "/a%20b"), None);
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[test]
+    fn reject_control_whitespace() {
+        let config = SanitizeConfig::new().reject_control_whitespace(true);
+
+        assert_eq!(
+            super::normalize(&config, "/safe%09/../secret"),
+            Err(SanitizeClass::ControlWhitespace)
+        );
+        assert_eq!(
+            super::normalize(&config, "/safe%0A"),
+            Err(SanitizeClass::ControlWhitespace)
+        );
+        assert_eq!(
+            normalize(&config, "/safe%20/../secret"),
+            Some(("/secret".to_string(), SanitizeClass::ParentDir))
+        );
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[test]
+    fn keep_decoded_percent_encoded() {
+        assert_eq!(
+            normalize(&SanitizeConfig::DEFAULT, "/%252e%252e/secret"),
+            None
+        );
+        assert_eq!(
+            normalize(&SanitizeConfig::DEFAULT, "/100%"),
+            Some(("/100%25".to_string(), SanitizeClass::Decoded))
+        );
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[test]
+    fn custom_decoder() {
+        let config = SanitizeConfig::new().decoder(|path| match decode(path) {
+            decoded if decoded.contains('+') => Cow::Owned(decoded.replace('+', " ")),
+            decoded => decoded,
+        });
+
+        assert_eq!(
+            normalize(&config, "/a+b/%2e%2e/c+d"),
+            Some(("/c%20d".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(normalize(&config, "/a/b"), None);
+        assert_eq!(config.clone(), config);
+        assert_ne!(config, SanitizeConfig::new().decoder(decode));
+    }
+
+    #[test]
+    fn strip_matrix_params() {
+        let config = SanitizeConfig::new().strip_matrix_params(true);
+
+        assert_eq!(
+            normalize(&config, "/..;/secret"),
+            Some(("/secret".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(
+            normalize(&config, "/a/.;x=1/b"),
+            Some(("/a/b".to_string(), SanitizeClass::CurrentDir))
+        );
+        assert_eq!(
+            normalize(&config, "/a;jsessionid=1/b;v=2"),
+            Some(("/a/b".to_string(), SanitizeClass::MatrixParam))
+        );
+        assert_eq!(normalize(&SanitizeConfig::DEFAULT, "/..;/secret"), None);
+    }
+
+    #[test]
+    fn empty_result() {
+        let config = SanitizeConfig::new();
+        assert_eq!(
+            normalize(&config, "/../.."),
+            Some(("/".to_string(), SanitizeClass::ParentDir))
+        );
+
+        let config = SanitizeConfig::new().empty_result(EmptyResult::Reject);
+        assert_eq!(
+            super::normalize(&config, "/a/../.."),
+            Err(SanitizeClass::ParentDir)
+        );
+        assert_eq!(normalize(&config, "/"), None);
+
+        let config = SanitizeConfig::new().empty_result(EmptyResult::Empty);
+        assert_eq!(
+            normalize(&config, "/../.."),
+            Some((String::new(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(normalize(&config, "/"), None);
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[test]
+    fn clean_fast_path() {
+        let configs = [
+            SanitizeConfig::new(),
+            SanitizeConfig::new().keep_trailing_slash(false),
+            SanitizeConfig::new().strip_matrix_params(true),
+            SanitizeConfig::new().max_segment_len(3),
+        ];
+        let paths = [
+            "/",
+            "",
+            "/a",
+            "/a/b.txt",
+            "/a/",
+            "/a.b/c..d",
+            "/a;b",
+            "a/b",
+            "//",
+            "/a//b",
+            "/.",
+            "/..",
+            "/a/.",
+            "/a/..",
+            "/%41",
+            "/a b",
+            "/./a",
+            ".",
+        ];
+
+        for config in configs {
+            // A custom decoder always takes the slow path
+            let slow = config.clone().decoder(|path: &str| Cow::Borrowed(path));
+
+            for path in paths {
+                if is_clean(&config, path, PathSource::Str) {
+                    assert_eq!(normalize(&slow, path), None, "{path}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn clean_paths() {
+        assert!(is_clean(&SanitizeConfig::DEFAULT, "/", PathSource::Str));
+        assert!(is_clean(
+            &SanitizeConfig::DEFAULT,
+            "/api/v1/users/42/",
+            PathSource::Str
+        ));
+        assert!(!is_clean(
+            &SanitizeConfig::DEFAULT,
+            "/api/%2e%2e",
+            PathSource::Str
+        ));
+        assert!(!is_clean(
+            &SanitizeConfig::DEFAULT,
+            "/api//users",
+            PathSource::Str
+        ));
+        assert!(!is_clean(
+            &SanitizeConfig::DEFAULT,
+            "/api/./users",
+            PathSource::Str
+        ));
+    }
+
+    #[cfg(feature = "fast-scan")]
+    #[test]
+    fn fast_scan_matches_scalar() {
+        // A maximum segment length takes the scalar scan without changing what is dirty
+        let scalar = SanitizeConfig::new().max_segment_len(usize::MAX);
+        let paths = [
+            "",
+            "a",
+            "a/",
+            "a//b",
+            "a/.b",
+            "./a",
+            "/a",
+            ".a",
+            "a.",
+            "a/b.c/d",
+            "a%2e",
+            "a;b",
+            "a\\b",
+            "a/b/c/d/e/f/g/h/i/j/k/l/m/n/o/p/q/r/s/t/u/v/w/x/y/z/0/1/2/3/4/5/6/7/8/9",
+            "static/assets/css/vendor/bootstrap-5.3.0/dist/bootstrap.min.css/",
+            "static/assets/css/vendor/bootstrap-5.3.0/dist/bootstrap.min.css/.",
+            "static/assets/css/vendor/bootstrap-5.3.0/dist/bootstrap.min.css%",
+            "static/assets/css/vendor/bootstrap-5.3.0/dist/bootstrap.min.css/..",
+        ];
+
+        for path in paths {
+            // Only paths `http::Uri` accepts take the fast scan
+            assert!(format!("/{path}").parse::<http::Uri>().is_ok(), "{path}");
+            assert_eq!(
+                scan_clean_fast(path.as_bytes()),
+                scan_clean(&scalar, path.as_bytes(), PathSource::Str),
+                "{path}"
+            );
+        }
+
+        // Bytes of other strings are still checked
+        assert!(!is_clean(&SanitizeConfig::DEFAULT, "/a b", PathSource::Str));
+        assert!(!is_clean(
+            &SanitizeConfig::DEFAULT,
+            "/a\u{e9}",
+            PathSource::Str
+        ));
+    }
+
+    #[test]
+    fn backslash_separator() {
+        let config = SanitizeConfig::new().backslash_separator(true);
+
+        assert_eq!(
+            normalize(&config, "/a\\b"),
+            Some(("/a/b".to_string(), SanitizeClass::Backslash))
+        );
+        assert_eq!(
+            normalize(&config, "/a/..\\..\\secret"),
+            Some(("/secret".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(normalize(&SanitizeConfig::DEFAULT, "/a\\b"), None);
+    }
+
+    #[test]
+    fn clean_query_policies() {
+        let query = "a=%00b&c=%0a%7F%20";
+
+        assert_eq!(clean_query(QueryControlPolicy::Keep, query), Ok(None));
+        assert_eq!(
+            clean_query(QueryControlPolicy::Strip, query),
+            Ok(Some("a=b&c=%20".to_string()))
+        );
+        assert_eq!(
+            clean_query(QueryControlPolicy::Reject, query),
+            Err(SanitizeClass::QueryControl)
+        );
+
+        assert_eq!(
+            clean_query(QueryControlPolicy::Reject, "a=%20%2&b=%"),
+            Ok(None)
+        );
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[test]
+    fn max_decode_ratio() {
+        // Every round doubles the path
+        let config = SanitizeConfig::new()
+            .decoder(|path: &str| Cow::Owned(path.repeat(2)))
+            .decode_rounds(3);
+
+        assert!(normalize(&config, "/a").is_some());
+        assert_eq!(
+            super::normalize(&config.clone().max_decode_ratio(4), "/a"),
+            Err(SanitizeClass::DecodeAmplification)
+        );
+        assert!(super::normalize(&config.max_decode_ratio(8), "/a").is_ok());
+
+        // The built-in decoder never lengthens a path, not even with the tightest ratio
+        let config = SanitizeConfig::new().decode_rounds(3).max_decode_ratio(1);
+        assert!(normalize(&config, "/%2e%2e/a").is_some());
+        assert!(normalize(&config, "/%25252e%25252e/a").is_some());
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[test]
+    fn max_segment_len() {
+        let long = "a".repeat(300);
+        let path = format!("/files/{long}/x");
+
+        let config = SanitizeConfig::new().max_segment_len(255);
+        assert_eq!(
+            super::normalize(&config, &path),
+            Err(SanitizeClass::OversizedSegment)
+        );
+        assert_eq!(normalize(&config, "/files/a/x"), None);
+
+        // Decoded before being measured
+        let encoded = "%61".repeat(255);
+        assert_eq!(
+            normalize(&config, &format!("/{encoded}")).unwrap().0.len(),
+            256
+        );
+
+        let config = config.oversized_segment(OversizedSegment::Truncate);
+        assert_eq!(
+            normalize(&config, &path),
+            Some((
+                format!("/files/{}/x", &long[..255]),
+                SanitizeClass::OversizedSegment
+            ))
+        );
+
+        // Never splits a character
+        let config = SanitizeConfig::new()
+            .max_segment_len(4)
+            .oversized_segment(OversizedSegment::Truncate);
+        assert_eq!(
+            normalize(&config, "/a%C3%A9%C3%A9/b"),
+            Some(("/a%C3%A9/b".to_string(), SanitizeClass::OversizedSegment))
+        );
+
+        let config = config.max_segment_len(2);
+        assert_eq!(
+            normalize(&config, "/x/..aaa/b"),
+            Some(("/b".to_string(), SanitizeClass::ParentDir))
+        );
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[test]
+    fn invalid_utf8() {
+        assert_eq!(
+            normalize(&SanitizeConfig::DEFAULT, "/%ff/a"),
+            Some(("/%EF%BF%BD/a".to_string(), SanitizeClass::Decoded))
+        );
+
+        let config = SanitizeConfig::new().invalid_utf8(InvalidUtf8::Reject);
+        assert_eq!(
+            super::normalize(&config, "/%ff/a"),
+            Err(SanitizeClass::InvalidUtf8)
+        );
+        assert_eq!(
+            normalize(&config, "/%D0%B9/../%e6%97%a5"),
+            Some(("/%E6%97%A5".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(normalize(&config, "/%D0%B9/x"), None);
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[test]
+    fn surrogates_and_noncharacters() {
+        // `U+D800` in WTF-8
+        assert_eq!(
+            normalize(&SanitizeConfig::DEFAULT, "/%ed%a0%80/a"),
+            Some((
+                "/%EF%BF%BD%EF%BF%BD%EF%BF%BD/a".to_string(),
+                SanitizeClass::Decoded
+            ))
+        );
+        let reject = SanitizeConfig::new().invalid_utf8(InvalidUtf8::Reject);
+        assert_eq!(
+            super::normalize(&reject, "/%ed%a0%80/a"),
+            Err(SanitizeClass::InvalidUtf8)
+        );
+
+        // `U+FFFE` and `U+FDD0` are valid UTF-8
+        assert_eq!(
+            normalize(&SanitizeConfig::DEFAULT, "/%ef%bf%be%ef%b7%90"),
+            Some(("/%EF%BF%BE%EF%B7%90".to_string(), SanitizeClass::Decoded))
+        );
+        assert_eq!(normalize(&reject, "/%EF%BF%BE"), None);
+
+        let replace = SanitizeConfig::new().noncharacters_invalid(true);
+        assert_eq!(
+            normalize(&replace, "/%ef%bf%be%ef%b7%90/%F4%8F%BF%BF"),
+            Some((
+                "/%EF%BF%BD%EF%BF%BD/%EF%BF%BD".to_string(),
+                SanitizeClass::Decoded
+            ))
+        );
+        assert_eq!(
+            normalize(&replace, "/%EF%BF%BD/%EF%B7%AF%C3%A9"),
+            Some((
+                "/%EF%BF%BD/%EF%BF%BD%C3%A9".to_string(),
+                SanitizeClass::Decoded
+            ))
+        );
+
+        let reject = reject.noncharacters_invalid(true);
+        assert_eq!(
+            super::normalize(&reject, "/a/%EF%BF%BE"),
+            Err(SanitizeClass::InvalidUtf8)
+        );
+        assert_eq!(normalize(&reject, "/%C3%A9"), None);
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn dot_homoglyphs() {
+        let config = SanitizeConfig::new().dot_homoglyphs(true);
+
+        // Fullwidth full stops
+        assert_eq!(
+            normalize(&config, "/a/%EF%BC%8E%EF%BC%8E/secret"),
+            Some(("/secret".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(
+            normalize(&config, "/%EF%BC%8E%EF%BC%8E/%EF%BC%8E%EF%BC%8E/secret"),
+            Some(("/secret".to_string(), SanitizeClass::ParentDir))
+        );
+        // Ideographic full stop, also mixed with an ASCII dot
+        assert_eq!(
+            normalize(&config, "/a/b/.%E3%80%82/c"),
+            Some(("/a/c".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(
+            normalize(&config, "/a/%E3%80%82/c"),
+            Some(("/a/c".to_string(), SanitizeClass::CurrentDir))
+        );
+
+        // Only whole dot-segments are folded
+        assert_eq!(normalize(&config, "/a/%EF%BC%8E%EF%BC%8E%EF%BC%8E"), None);
+        assert_eq!(normalize(&config, "/file%E3%80%82txt"), None);
+
+        assert_eq!(
+            normalize(&SanitizeConfig::DEFAULT, "/a/%EF%BC%8E%EF%BC%8E/secret"),
+            None
+        );
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn unicode_nfc() {
+        let config = SanitizeConfig::new().unicode_nfc(true);
+
+        // `й` decomposed into `и` and a combining breve
+        let (path, class) = normalize(&config, "/%D0%B8%CC%86/x").unwrap();
+        assert_eq!(path, "/%D0%B9/x");
+        assert_eq!(class, SanitizeClass::Decoded);
+        assert_eq!(normalize(&config, &path), None);
+
+        // Already composed paths are stable
+        assert_eq!(normalize(&config, "/%D0%B9/%E6%97%A5%E6%9C%AC"), None);
+        assert_eq!(normalize(&SanitizeConfig::DEFAULT, "/%D0%B8%CC%86/x"), None);
+    }
+
+    #[test]
+    fn root_behavior() {
+        let config = SanitizeConfig::new();
+        assert_eq!(
+            normalize(&config, "a/../b"),
+            Some(("b".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(normalize(&config, "a/b"), None);
+
+        let config = SanitizeConfig::new().root_behavior(RootBehavior::ForceRoot);
+        assert_eq!(
+            normalize(&config, "a/../b"),
+            Some(("/b".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(
+            normalize(&config, "a/b"),
+            Some(("/a/b".to_string(), SanitizeClass::Decoded))
+        );
+        assert_eq!(normalize(&config, "*"), None);
+        assert_eq!(normalize(&config, ""), None);
+
+        let config = SanitizeConfig::new().root_behavior(RootBehavior::Strip);
+        assert_eq!(
+            normalize(&config, "/a/../b"),
+            Some(("b".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(
+            normalize(&config, "/"),
+            Some((String::new(), SanitizeClass::Decoded))
+        );
+        assert_eq!(normalize(&config, "a/../b").unwrap().0, "b");
+        assert_eq!(normalize(&config, "a/b"), None);
+    }
+
+    #[test]
+    fn relative_encoded_traversal() {
+        let relative = SanitizeConfig::new();
+        let rooted = SanitizeConfig::new().root_behavior(RootBehavior::ForceRoot);
+
+        for path in ["%2e%2e%2fsecret", "..%2fsecret", "%2E%2E%2Fsecret"] {
+            assert_eq!(
+                normalize(&relative, path),
+                Some(("secret".to_string(), SanitizeClass::ParentDir))
+            );
+            assert_eq!(
+                normalize(&rooted, path),
+                Some(("/secret".to_string(), SanitizeClass::ParentDir))
+            );
+        }
+
+        // Parents are popped, and traversing above the start stays there
+        assert_eq!(
+            normalize(&relative, "a%2fb%2f%2e%2e%2f%2e%2e%2f%2e%2e%2fsecret"),
+            Some(("secret".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(
+            normalize(&relative, "a%2f%2e%2e%2fb%2f..%2fsecret"),
+            Some(("secret".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(
+            normalize(&relative, "%2e%2e"),
+            Some((String::new(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(
+            normalize(&rooted, "%2e%2e"),
+            Some(("/".to_string(), SanitizeClass::ParentDir))
+        );
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[test]
+    fn reject_on_encoded_separator() {
+        let config = SanitizeConfig::new().reject_on_encoded_separator(true);
+
+        assert_eq!(
+            normalize_with(&config, "/a%2fb", PathSource::Str, None),
+            Err(SanitizeClass::EncodedSeparator)
+        );
+        assert_eq!(
+            normalize_with(&config, "/a/%2e%2e%2Fsecret", PathSource::Str, None),
+            Err(SanitizeClass::EncodedSeparator)
+        );
+        assert_eq!(
+            normalize(&config, "/a%20b/../c"),
+            Some(("/c".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(
+            normalize(&config, "/a/%2e%2e/b"),
+            Some(("/b".to_string(), SanitizeClass::ParentDir))
+        );
+
+        let config = config.backslash_separator(true);
+        assert_eq!(
+            normalize_with(&config, "/a%5cb", PathSource::Str, None),
+            Err(SanitizeClass::EncodedSeparator)
+        );
+        assert_eq!(
+            normalize(&config, "/a\\b"),
+            Some(("/a/b".to_string(), SanitizeClass::Backslash))
+        );
+
+        assert_eq!(
+            normalize(&SanitizeConfig::new(), "/a%2fb"),
+            Some(("/a/b".to_string(), SanitizeClass::Decoded))
+        );
+    }
+
+    #[test]
+    fn virtual_root() {
+        let config = SanitizeConfig::new().virtual_root("/app");
+
+        assert_eq!(
+            normalize(&config, "/app/../../etc"),
+            Some(("/app".to_string(), SanitizeClass::RootEscape))
+        );
+        assert_eq!(
+            normalize(&config, "/app/%2e%2e/etc"),
+            Some(("/app".to_string(), SanitizeClass::RootEscape))
+        );
+        assert_eq!(
+            normalize(&config, "/app/a/../b"),
+            Some(("/app/b".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(normalize(&config, "/app"), None);
+        assert_eq!(normalize(&config, "/app/"), None);
+        assert_eq!(normalize(&config, "/app/x"), None);
+        // Only whole segments match the root
+        assert_eq!(
+            normalize(&config, "/application"),
+            Some(("/app".to_string(), SanitizeClass::RootEscape))
+        );
+
+        let config = config.root_escape(RootEscape::Reject);
+        assert_eq!(
+            normalize_with(&config, "/app/../../etc", PathSource::Str, None),
+            Err(SanitizeClass::RootEscape)
+        );
+        assert_eq!(
+            normalize_with(&config, "/etc", PathSource::Str, None),
+            Err(SanitizeClass::RootEscape)
+        );
+        assert_eq!(normalize(&config, "/app/x/../y").unwrap().0, "/app/y");
+
+        // A trailing slash on the root is ignored
+        let config = SanitizeConfig::new().virtual_root("/app/");
+        assert_eq!(normalize(&config, "/app/../etc").unwrap().0, "/app");
+        assert_eq!(
+            normalize(&SanitizeConfig::new().virtual_root("/"), "/a/../b")
+                .unwrap()
+                .0,
+            "/b"
+        );
+    }
+
+    #[test]
+    fn aggressive_dots() {
+        let config = SanitizeConfig::new().aggressive_dots(true);
+
+        assert_eq!(
+            normalize(&config, "/a/..../secret"),
+            Some(("/secret".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(
+            normalize(&config, "/a/b/.../%2e%2e%2e/secret"),
+            Some(("/secret".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(normalize(&config, "/a/...b/c"), None);
+
+        let config = SanitizeConfig::new();
+        assert_eq!(normalize(&config, "/..../secret"), None);
+        assert_eq!(normalize(&config, "/a/.../b"), None);
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[test]
+    fn exported_constants() {
+        let config = SanitizeConfig::new();
+        for segment in DANGEROUS_SEGMENTS {
+            let (path, _) = normalize(&config, &format!("/a/b/{segment}/c")).unwrap();
+            assert!(!path.split('/').any(|s| s == *segment), "{path}");
+        }
+        assert_eq!(normalize(&config, "/a/b/.../c"), None);
+
+        let config = SanitizeConfig::new().reject_control_whitespace(true);
+        for byte in 0..=0x7fu8 {
+            let path = format!("/a%{byte:02x}b");
+            let rejected = normalize_with(&config, &path, PathSource::Str, None).is_err();
+            assert_eq!(
+                rejected,
+                CONTROL_WHITESPACE.contains(&(byte as char)),
+                "{path}"
+            );
+        }
+
+        for byte in 0..=0xffu8 {
+            let query = format!("a%{byte:02x}b");
+            let stripped = clean_query(QueryControlPolicy::Strip, &query).unwrap();
+            assert_eq!(stripped.is_some(), CONTROL_CHARS.contains(&byte), "{query}");
+        }
+    }
+
+    #[cfg(feature = "percent-decode")]
+    #[test]
+    fn strip_control_chars() {
+        let config = SanitizeConfig::new().strip_control_chars(true);
+
+        for path in ["/a/..%00/secret", "/a/..\0/secret", "/a/.%01.%7f/secret"] {
+            assert_eq!(
+                normalize(&config, path),
+                Some(("/secret".to_string(), SanitizeClass::ParentDir)),
+                "{path}"
+            );
+        }
+        assert_eq!(
+            normalize(&config, "/a%00b"),
+            Some(("/ab".to_string(), SanitizeClass::ControlChar))
+        );
+
+        // Otherwise a strange but normal segment
+        let config = SanitizeConfig::new();
+        assert_eq!(normalize(&config, "/a/..%00/secret"), None);
+        assert_eq!(
+            normalize(&config, "/a/..\0/secret"),
+            Some(("/a/..%00/secret".to_string(), SanitizeClass::Decoded))
+        );
+    }
+
+    #[test]
+    fn go_clean() {
+        // The `cleantests` of Go's `path` package, except for the empty path
+        const CASES: &[(&str, &str)] = &[
+            // Already clean
+            ("abc", "abc"),
+            ("abc/def", "abc/def"),
+            ("a/b/c", "a/b/c"),
+            (".", "."),
+            ("..", ".."),
+            ("../..", "../.."),
+            ("../../abc", "../../abc"),
+            ("/abc", "/abc"),
+            ("/", "/"),
+            // Remove trailing slash
+            ("abc/", "abc"),
+            ("abc/def/", "abc/def"),
+            ("a/b/c/", "a/b/c"),
+            ("./", "."),
+            ("../", ".."),
+            ("../../", "../.."),
+            ("/abc/", "/abc"),
+            // Remove doubled slash
+            ("abc//def//ghi", "abc/def/ghi"),
+            ("//abc", "/abc"),
+            ("///abc", "/abc"),
+            ("//abc//", "/abc"),
+            ("abc//", "abc"),
+            // Remove . elements
+            ("abc/./def", "abc/def"),
+            ("/./abc/def", "/abc/def"),
+            ("abc/.", "abc"),
+            // Remove .. elements
+            ("abc/def/ghi/../jkl", "abc/def/jkl"),
+            ("abc/def/../ghi/../jkl", "abc/jkl"),
+            ("abc/def/..", "abc"),
+            ("abc/def/../..", "."),
+            ("/abc/def/../..", "/"),
+            ("abc/def/../../..", ".."),
+            ("/abc/def/../../..", "/"),
+            ("abc/def/../../../ghi/jkl/../../../mno", "../../mno"),
+            // Combinations
+            ("abc/./../def", "def"),
+            ("abc//./../def", "def"),
+            ("abc/../../././../def", "../../def"),
+        ];
+
+        let config = SanitizeConfig::new().path_strategy(PathStrategy::GoClean);
+        for &(input, expected) in CASES {
+            let path = normalize(&config, input).map_or(input.to_string(), |(path, _)| path);
+            assert_eq!(path, expected, "{input}");
+            assert_eq!(normalize(&config, expected), None, "{expected}");
+        }
+
+        assert_eq!(normalize(&config, ""), None);
+        // Nothing is decoded
+        assert_eq!(normalize(&config, "/%2e%2e/abc"), None);
+        assert_eq!(
+            normalize(&config, "/a/../../b/"),
+            Some(("/b".to_string(), SanitizeClass::ParentDir))
+        );
+        assert_eq!(
+            normalize(&config, "/a//b"),
+            Some(("/a/b".to_string(), SanitizeClass::EmptySegment))
+        );
+    }
+}
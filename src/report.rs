@@ -0,0 +1,112 @@
+//! Detailed reports of what sanitization changed, for forensic logging.
+
+use crate::{
+    normalize::{normalize_default, Normalized, PathSource},
+    SanitizeClass,
+};
+
+/// What sanitizing a path with the default [`SanitizeConfig`](crate::SanitizeConfig) changed, from [`sanitize_report`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SanitizeReport {
+    /// The path as it was given.
+    pub original: String,
+    /// The sanitized path, equal to `original` when nothing changed.
+    pub sanitized: String,
+    /// Why the path changed, or `None` if it did not.
+    pub class: Option<SanitizeClass>,
+    /// Every segment removed or modified, in the order they were seen.
+    pub changes: Vec<SegmentChange>,
+}
+
+/// A segment removed or modified by sanitization.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SegmentChange {
+    /// The decoded segment, as it was before being changed.
+    pub segment: String,
+    /// Why the segment was changed.
+    ///
+    /// [`SanitizeClass::ParentDir`] is used both for `..` segments and the segment each one
+    /// steps out of.
+    pub class: SanitizeClass,
+}
+
+/// What [`normalize_with`] changed while sanitizing a path.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Changes {
+    /// Every segment removed or modified, in the order they were seen.
+    pub(crate) segments: Vec<SegmentChange>,
+    /// How many decode rounds changed the path.
+    pub(crate) decode_rounds: u8,
+}
+
+/// Sanitize `path` with the default [`SanitizeConfig`](crate::SanitizeConfig), reporting every segment removed or
+/// modified along the way.
+///
+/// ```
+/// use tower_sanitize_path::{sanitize_report, SanitizeClass};
+///
+/// let report = sanitize_report("/a/../../secret");
+/// assert_eq!(report.sanitized, "/secret");
+///
+/// let removed: Vec<_> = report.changes.iter().map(|c| c.segment.as_str()).collect();
+/// assert_eq!(removed, ["a", "..", ".."]);
+/// ```
+pub fn sanitize_report(path: &str) -> SanitizeReport {
+    let mut changes = Changes::default();
+
+    let (sanitized, class) = match normalize_default(path, PathSource::Str, Some(&mut changes)) {
+        Some(Normalized { path, class, .. }) => (path, Some(class)),
+        None => (path.to_owned(), None),
+    };
+
+    SanitizeReport {
+        original: path.to_owned(),
+        sanitized,
+        class,
+        changes: changes.segments,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(segment: &str, class: SanitizeClass) -> SegmentChange {
+        SegmentChange {
+            segment: segment.to_string(),
+            class,
+        }
+    }
+
+    #[test]
+    fn multi_traversal() {
+        let report = sanitize_report("/a/b/./../%2e%2e/..//c");
+
+        assert_eq!(report.original, "/a/b/./../%2e%2e/..//c");
+        assert_eq!(report.sanitized, "/c");
+        assert_eq!(report.class, Some(SanitizeClass::ParentDir));
+        assert_eq!(
+            report.changes,
+            [
+                change(".", SanitizeClass::CurrentDir),
+                change("b", SanitizeClass::ParentDir),
+                change("..", SanitizeClass::ParentDir),
+                change("a", SanitizeClass::ParentDir),
+                change("..", SanitizeClass::ParentDir),
+                change("..", SanitizeClass::ParentDir),
+                change("", SanitizeClass::EmptySegment),
+            ]
+        );
+    }
+
+    #[test]
+    fn clean_path() {
+        let report = sanitize_report("/a/b");
+
+        assert_eq!(report.sanitized, "/a/b");
+        assert_eq!(report.class, None);
+        assert!(report.changes.is_empty());
+    }
+}
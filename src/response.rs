@@ -0,0 +1,186 @@
+//! Responses created by [`SanitizePath`](crate::SanitizePath) itself.
+
+use http::{Request, Response, StatusCode};
+
+use crate::{SanitizeClass, SanitizeConfig};
+
+/// Response bodies [`SanitizePath`](crate::SanitizePath) can create its own responses with.
+///
+/// This is implemented for every body implementing [`Default`].
+pub trait ResponseBody: Sized {
+    /// An empty body.
+    fn empty() -> Self;
+}
+
+impl<B: Default> ResponseBody for B {
+    fn empty() -> Self {
+        B::default()
+    }
+}
+
+/// Builds the responses to requests rejected by [`SanitizePath`](crate::SanitizePath).
+///
+/// Implemented for closures taking the rejected request, the status it would be rejected with
+/// and why it was rejected, set with
+/// [`SanitizePath::with_reject_response_builder`](crate::SanitizePath::with_reject_response_builder).
+pub trait RejectResponse<ReqBody, ResBody> {
+    /// Build the response to `request`, which `config` rejected with `status` because of `class`.
+    fn reject(
+        &self,
+        request: &Request<ReqBody>,
+        config: &SanitizeConfig,
+        status: StatusCode,
+        class: SanitizeClass,
+    ) -> Response<ResBody>;
+}
+
+impl<F, ReqBody, ResBody> RejectResponse<ReqBody, ResBody> for F
+where
+    F: Fn(&Request<ReqBody>, StatusCode, SanitizeClass) -> Response<ResBody>,
+{
+    fn reject(
+        &self,
+        request: &Request<ReqBody>,
+        _config: &SanitizeConfig,
+        status: StatusCode,
+        class: SanitizeClass,
+    ) -> Response<ResBody> {
+        self(request, status, class)
+    }
+}
+
+/// The default [`RejectResponse`], an empty response with the rejection status.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultRejection;
+
+impl<ReqBody, ResBody: ResponseBody> RejectResponse<ReqBody, ResBody> for DefaultRejection {
+    fn reject(
+        &self,
+        _request: &Request<ReqBody>,
+        _config: &SanitizeConfig,
+        status: StatusCode,
+        _class: SanitizeClass,
+    ) -> Response<ResBody> {
+        status_response(status)
+    }
+}
+
+/// A [`RejectResponse`] giving rejections an RFC 7807 `application/problem+json` body describing
+/// why the request was rejected.
+///
+/// ```
+/// use http::{Request, Response};
+/// use tower::service_fn;
+/// use tower_sanitize_path::{ProblemJson, SanitizeConfig, SanitizePath};
+///
+/// let config = SanitizeConfig::new().reject_on_traversal(true);
+/// let service = SanitizePath::with_config(
+///     service_fn(|_: Request<()>| async {
+///         Ok::<_, std::convert::Infallible>(Response::new(String::new()))
+///     }),
+///     config,
+/// )
+/// .reject_response_builder(ProblemJson);
+/// ```
+#[cfg(feature = "json")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProblemJson;
+
+#[cfg(feature = "json")]
+impl<ReqBody, ResBody: From<String>> RejectResponse<ReqBody, ResBody> for ProblemJson {
+    fn reject(
+        &self,
+        _request: &Request<ReqBody>,
+        _config: &SanitizeConfig,
+        status: StatusCode,
+        class: SanitizeClass,
+    ) -> Response<ResBody> {
+        use http::{header, HeaderValue};
+
+        let problem = serde_json::json!({
+            "type": "about:blank",
+            "title": status.canonical_reason().unwrap_or_default(),
+            "status": status.as_u16(),
+            "detail": class.description(),
+        });
+
+        let mut response = Response::new(ResBody::from(problem.to_string()));
+        *response.status_mut() = status;
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        );
+
+        response
+    }
+}
+
+/// An empty response with the given status.
+pub(crate) fn status_response<B: ResponseBody>(status: StatusCode) -> Response<B> {
+    let mut response = Response::new(B::empty());
+    *response.status_mut() = status;
+
+    response
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use http::header;
+
+    use super::*;
+
+    fn reject<R: RejectResponse<(), String>>(reject: R) -> Response<String> {
+        let request = Request::new(());
+        let config = SanitizeConfig::new().reject_on_traversal(true);
+
+        reject.reject(
+            &request,
+            &config,
+            StatusCode::BAD_REQUEST,
+            SanitizeClass::ParentDir,
+        )
+    }
+
+    #[test]
+    fn problem_json_rejection() {
+        let response = reject(ProblemJson);
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/problem+json"
+        );
+
+        let body: serde_json::Value = serde_json::from_str(response.body()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "type": "about:blank",
+                "title": "Bad Request",
+                "status": 400,
+                "detail": "path traversal detected",
+            })
+        );
+    }
+
+    #[test]
+    fn json_is_additive() {
+        use tower::{service_fn, Service};
+
+        fn assert_service<S: Service<Request<()>>>(_: S) {}
+
+        // Bodies without `From<String>` still work with the default rejections
+        let inner = service_fn(|_: Request<()>| async {
+            Ok::<_, std::convert::Infallible>(Response::new(()))
+        });
+        assert_service(crate::SanitizePath::sanitize_paths(inner));
+    }
+
+    #[test]
+    fn empty_default_rejection() {
+        let response = reject(DefaultRejection);
+
+        assert_eq!(response.body(), "");
+        assert!(!response.headers().contains_key(header::CONTENT_TYPE));
+    }
+}
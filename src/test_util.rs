@@ -0,0 +1,106 @@
+//! Helpers for testing services behind [`SanitizePath`](crate::SanitizePath).
+//!
+//! Requires the `test-util` feature.
+
+use std::{
+    convert::Infallible,
+    future::{ready, Ready},
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use http::{Request, Response, Uri};
+use tower_service::Service;
+
+use crate::classify_and_sanitize;
+
+/// Sanitize `uri` with the default config, returning the URI an inner service would see.
+///
+/// # Panics
+///
+/// Panics if `uri` is not a valid URI.
+///
+/// ```
+/// use tower_sanitize_path::test_util::sanitize_for_test;
+///
+/// assert_eq!(sanitize_for_test("/static/../../secret?q"), "/secret?q");
+/// ```
+pub fn sanitize_for_test(uri: &str) -> String {
+    let uri = uri.parse().expect("a valid uri");
+
+    classify_and_sanitize(uri).0.to_string()
+}
+
+/// A service recording the URI of every request it receives, responding with an empty body.
+///
+/// Clones share the same record, so keep a clone to inspect after handing one to a layer.
+#[derive(Clone, Debug, Default)]
+pub struct MockSink {
+    uris: Arc<Mutex<Vec<Uri>>>,
+}
+
+impl MockSink {
+    /// Create a sink which has not received any requests.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The URIs of every request received so far, in order.
+    pub fn uris(&self) -> Vec<Uri> {
+        self.uris.lock().expect("lock not poisoned").clone()
+    }
+
+    /// The URI of the most recent request, if any.
+    pub fn last_uri(&self) -> Option<Uri> {
+        self.uris.lock().expect("lock not poisoned").last().cloned()
+    }
+}
+
+impl<ReqBody> Service<Request<ReqBody>> for MockSink {
+    type Response = Response<String>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        self.uris
+            .lock()
+            .expect("lock not poisoned")
+            .push(req.uri().clone());
+
+        ready(Ok(Response::new(String::new())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower::{ServiceBuilder, ServiceExt};
+
+    use super::*;
+    use crate::SanitizePathLayer;
+
+    #[test]
+    fn sanitize() {
+        assert_eq!(sanitize_for_test("/a/./b/../c"), "/a/c");
+        assert_eq!(sanitize_for_test("/clean?q=1"), "/clean?q=1");
+    }
+
+    #[tokio::test]
+    async fn sink_records_sanitized_uris() {
+        let sink = MockSink::new();
+        let svc = ServiceBuilder::new()
+            .layer(SanitizePathLayer::new())
+            .service(sink.clone());
+
+        for uri in ["/../secret", "/clean"] {
+            let request = Request::builder().uri(uri).body(()).unwrap();
+            svc.clone().oneshot(request).await.unwrap();
+        }
+
+        assert_eq!(sink.uris(), ["/secret", "/clean"]);
+        assert_eq!(sink.last_uri().unwrap(), "/clean");
+    }
+}
@@ -0,0 +1,223 @@
+//! Tracing of sanitized requests.
+//!
+//! Without the `tracing` feature every function here is empty and inlined away, leaving no
+//! tracing on the request path. The hooks feeding [`SanitizeAudit`](crate::SanitizeAudit) are
+//! separate and always compiled, see the `audit` module.
+
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+
+use crate::SanitizeClass;
+
+/// Record that the request with `path` needed sanitizing because of `class`, and what `action`
+/// was taken.
+#[cfg(feature = "tracing")]
+pub(crate) fn sanitized(action: &'static str, path: &str, class: SanitizeClass) {
+    tracing::debug!(action, path, ?class, "sanitized request path");
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline(always)]
+pub(crate) fn sanitized(_action: &'static str, _path: &str, _class: SanitizeClass) {}
+
+/// Warn that a request reached the middleware with the path-dependent extension `name` already
+/// set.
+#[cfg(feature = "tracing")]
+pub(crate) fn misplaced(name: &'static str) {
+    tracing::warn!(
+        extension = name,
+        "path-dependent extension set before sanitizing, SanitizePathLayer should run first"
+    );
+}
+
+/// Times how long sanitizing a request path takes.
+///
+/// The clock is only read when `TRACE` events are enabled.
+pub(crate) struct Timer {
+    #[cfg(feature = "tracing")]
+    start: Option<Instant>,
+}
+
+#[cfg(feature = "tracing")]
+impl Timer {
+    pub(crate) fn start() -> Self {
+        Self {
+            start: tracing::enabled!(tracing::Level::TRACE).then(Instant::now),
+        }
+    }
+
+    /// Record the time spent since the timer started on the request with `path`.
+    pub(crate) fn finish(self, path: &str) {
+        if let Some(start) = self.start {
+            let elapsed_ns = start.elapsed().as_nanos() as u64;
+            tracing::trace!(path, elapsed_ns, "timed request path sanitization");
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl Timer {
+    #[inline(always)]
+    pub(crate) fn start() -> Self {
+        Self {}
+    }
+
+    #[inline(always)]
+    pub(crate) fn finish(self, _path: &str) {}
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::{
+        convert::Infallible,
+        fmt::{self, Write},
+        sync::{Arc, Mutex},
+    };
+
+    use http::{Request, Response};
+    use tower::service_fn;
+    use tower_service::Service;
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Level, Metadata, Subscriber,
+    };
+
+    use crate::{SanitizeConfig, SanitizePath};
+
+    /// Collects the fields of every event up to `level` as a line of text.
+    #[derive(Clone)]
+    struct Capture {
+        lines: Arc<Mutex<Vec<String>>>,
+        level: Level,
+    }
+
+    struct Line(String);
+
+    impl Visit for Line {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            let _ = write!(self.0, "{}={:?} ", field.name(), value);
+        }
+    }
+
+    impl Subscriber for Capture {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            *metadata.level() <= self.level
+        }
+
+        fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+            span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut line = Line(String::new());
+            event.record(&mut line);
+            self.lines
+                .lock()
+                .unwrap()
+                .push(line.0.trim_end().to_string());
+        }
+
+        fn enter(&self, _: &span::Id) {}
+
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    fn events(config: SanitizeConfig, uri: &str) -> Vec<String> {
+        events_up_to(Level::DEBUG, config, uri)
+    }
+
+    fn events_up_to(level: Level, config: SanitizeConfig, uri: &str) -> Vec<String> {
+        let capture = Capture {
+            lines: Default::default(),
+            level,
+        };
+        let mut svc = SanitizePath::with_config(
+            service_fn(|_: Request<()>| async {
+                Ok::<_, Infallible>(Response::new(String::new()))
+            }),
+            config,
+        );
+
+        // Events are emitted by `call` itself, so the future never needs polling
+        tracing::subscriber::with_default(capture.clone(), || {
+            drop(svc.call(Request::builder().uri(uri).body(()).unwrap()));
+        });
+
+        let events = capture.lines.lock().unwrap().clone();
+        events
+    }
+
+    #[test]
+    fn rewrite_event() {
+        assert_eq!(
+            events(SanitizeConfig::new(), "/a/../b"),
+            [r#"message=sanitized request path action="rewrite" path="/a/../b" class=ParentDir"#]
+        );
+    }
+
+    #[test]
+    fn reject_event() {
+        assert_eq!(
+            events(SanitizeConfig::new().reject_on_traversal(true), "/a/../b"),
+            [r#"message=sanitized request path action="reject" path="/a/../b" class=ParentDir"#]
+        );
+    }
+
+    #[test]
+    fn no_event_for_clean_path() {
+        assert!(events(SanitizeConfig::new(), "/a/b").is_empty());
+    }
+
+    #[test]
+    fn misplaced_event() {
+        #[derive(Clone)]
+        struct MatchedRoute;
+
+        let capture = Capture {
+            lines: Default::default(),
+            level: Level::WARN,
+        };
+        let config = SanitizeConfig::new().path_dependent_extension::<MatchedRoute>();
+        let mut svc = SanitizePath::with_config(
+            service_fn(|_: Request<()>| async {
+                Ok::<_, Infallible>(Response::new(String::new()))
+            }),
+            config,
+        );
+
+        tracing::subscriber::with_default(capture.clone(), || {
+            drop(svc.call(Request::builder().uri("/a").body(()).unwrap()));
+            drop(
+                svc.call(
+                    Request::builder()
+                        .uri("/a")
+                        .extension(MatchedRoute)
+                        .body(())
+                        .unwrap(),
+                ),
+            );
+        });
+
+        let lines = capture.lines.lock().unwrap();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("message=path-dependent extension set before sanitizing"));
+        assert!(lines[0].ends_with(r#"misplaced_event::MatchedRoute""#));
+    }
+
+    #[test]
+    fn timing_event() {
+        let events = events_up_to(Level::TRACE, SanitizeConfig::new(), "/a/../b");
+        assert_eq!(events.len(), 2);
+        assert!(events[0]
+            .starts_with(r#"message=timed request path sanitization path="/a/../b" elapsed_ns="#));
+
+        // Clean paths are timed too
+        let events = events_up_to(Level::TRACE, SanitizeConfig::new(), "/a/b");
+        assert_eq!(events.len(), 1);
+        assert!(events[0].contains("elapsed_ns="));
+    }
+}
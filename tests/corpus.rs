@@ -0,0 +1,124 @@
+//! Known path traversal payloads, snapshotting how each one is sanitized.
+//!
+//! Outputs which still traverse on backends splitting paths on more than `/` are flagged in the
+//! snapshot, and the flagged set is asserted so fixing or regressing one fails the test.
+
+// The snapshot is of the default `DecodeMode::Full`
+#![cfg(feature = "percent-decode")]
+
+use std::{convert::Infallible, fmt::Write};
+
+use http::{Request, Response};
+use tower::{service_fn, Layer, ServiceExt};
+use tower_sanitize_path::{classify_and_sanitize, SanitizeConfig, SanitizePathLayer};
+
+/// Payloads collected from path traversal cheat sheets, each followed by `etc/passwd`.
+const PAYLOADS: &[&str] = &[
+    "/../",
+    "/../../../",
+    "/./../",
+    "/..//",
+    "/....//",
+    "/...//",
+    "/..;/",
+    "/.;/",
+    "/..\\",
+    "/..\\..\\",
+    "/%2e%2e/",
+    "/%2E%2E/",
+    "/%2e%2e%2f",
+    "/..%2f",
+    "/%2e./",
+    "/.%2e/",
+    "/%2e%2e%5c",
+    "/..%5c",
+    "/%252e%252e%252f",
+    "/..%252f",
+    "/..%c0%af",
+    "/..%c1%9c",
+    "/%c0%ae%c0%ae/",
+    "/%uff0e%uff0e/",
+    "/%ef%bc%8e%ef%bc%8e/",
+    "/..%00/",
+    "/..%0d/",
+    "/..%ff/",
+    "/..%u2215",
+    "/static/../../",
+    "/static/%2e%2e/%2e%2e/",
+    "//../",
+    "/%2f..%2f",
+    "/..%2f..%2f..%2f",
+    "/.%00./",
+    "/..%20/",
+    "/..?",
+    "/..#",
+];
+
+/// Whether `path` has a `..` segment, splitting on `/` and on any of `separators`.
+fn traverses(path: &str, separators: &[char]) -> bool {
+    path.split(|c| c == '/' || separators.contains(&c))
+        .any(|segment| segment == "..")
+}
+
+/// Whether `path` has a `..` segment once matrix parameters are stripped, as Tomcat does.
+fn traverses_without_matrix(path: &str) -> bool {
+    path.split('/')
+        .any(|segment| segment.split(';').next() == Some(".."))
+}
+
+#[test]
+fn attack_corpus() {
+    let mut snapshot = String::new();
+    let mut backslash = Vec::new();
+    let mut matrix = Vec::new();
+
+    for payload in PAYLOADS {
+        let uri = format!("{payload}etc/passwd");
+        let (sanitized, class) = classify_and_sanitize(uri.parse().unwrap());
+        let path = sanitized.path();
+
+        assert!(!traverses(path, &[]), "{uri} => {sanitized} traverses");
+
+        write!(snapshot, "{uri} => {sanitized} ({class:?})").unwrap();
+        if traverses(path, &['\\']) {
+            backslash.push(*payload);
+            snapshot.push_str(" [unsafe_for_backslash_backends]");
+        }
+        if traverses_without_matrix(path) {
+            matrix.push(*payload);
+            snapshot.push_str(" [unsafe_for_matrix_backends]");
+        }
+        snapshot.push('\n');
+    }
+
+    insta::assert_snapshot!(snapshot);
+
+    // Only safe with `backslash_separator`, the default leaves `\` to the inner service
+    assert_eq!(
+        backslash,
+        ["/..\\", "/..\\..\\", "/%2e%2e%5c", "/..%5c"],
+        "backslash traversals changed"
+    );
+    // Only safe with `strip_matrix_params`
+    assert_eq!(matrix, ["/..;/"], "matrix parameter traversals changed");
+}
+
+#[tokio::test]
+async fn flagged_payloads_safe_when_configured() {
+    let config = SanitizeConfig::new()
+        .backslash_separator(true)
+        .strip_matrix_params(true);
+    let layer = SanitizePathLayer::with_config(config);
+
+    for payload in ["/..\\", "/..\\..\\", "/%2e%2e%5c", "/..%5c", "/..;/"] {
+        let uri = format!("{payload}etc/passwd");
+        let service = layer.layer(service_fn(|req: Request<()>| async move {
+            Ok::<_, Infallible>(Response::new(req.uri().path().to_owned()))
+        }));
+        let request = Request::builder().uri(&uri).body(()).unwrap();
+        let path = service.oneshot(request).await.unwrap().into_body();
+
+        assert!(!traverses(&path, &['\\']), "{uri} => {path}");
+        assert!(!traverses_without_matrix(&path), "{uri} => {path}");
+    }
+}
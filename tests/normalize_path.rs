@@ -0,0 +1,73 @@
+//! Stacking `SanitizePathLayer` with tower-http's `NormalizePathLayer`, which trims trailing
+//! slashes.
+//!
+//! Both orders reach the same path, since trimming slashes never creates a traversal and
+//! sanitizing never adds a trailing slash. Sanitizing first is still recommended, so the
+//! `OriginalPath` extension holds the path exactly as the client sent it.
+
+use std::convert::Infallible;
+
+use http::{Request, Response};
+use tower::{service_fn, Layer, ServiceExt};
+use tower_http::normalize_path::NormalizePathLayer;
+use tower_sanitize_path::{OriginalPath, SanitizePathLayer};
+
+/// Respond with the path the inner service sees, and the recorded original path.
+async fn echo(req: Request<()>) -> Result<Response<String>, Infallible> {
+    let original = req.extensions().get::<OriginalPath>();
+    let body = format!("{} {:?}", req.uri(), original.map(|o| &o.0));
+
+    Ok(Response::new(body))
+}
+
+async fn sanitize_then_normalize(uri: &str) -> String {
+    let service = SanitizePathLayer::new()
+        .layer(NormalizePathLayer::trim_trailing_slash().layer(service_fn(echo)));
+    let request = Request::builder().uri(uri).body(()).unwrap();
+
+    service.oneshot(request).await.unwrap().into_body()
+}
+
+async fn normalize_then_sanitize(uri: &str) -> String {
+    let service = NormalizePathLayer::trim_trailing_slash()
+        .layer(SanitizePathLayer::new().layer(service_fn(echo)));
+    let request = Request::builder().uri(uri).body(()).unwrap();
+
+    service.oneshot(request).await.unwrap().into_body()
+}
+
+#[tokio::test]
+async fn same_path_in_both_orders() {
+    let cases = [
+        ("/", "/"),
+        ("/a/", "/a"),
+        ("/a/../b/", "/b"),
+        ("/a/..//", "/"),
+        ("//a//b//", "/a/b"),
+        ("/%2e%2e/", "/"),
+        ("/a/%2e%2e%2f", "/"),
+        ("/a/../b/?q=1", "/b?q=1"),
+    ];
+
+    for (uri, expected) in cases {
+        let sanitized_first = sanitize_then_normalize(uri).await;
+        let normalized_first = normalize_then_sanitize(uri).await;
+
+        assert_eq!(sanitized_first.split(' ').next(), Some(expected), "{uri}");
+        assert_eq!(normalized_first.split(' ').next(), Some(expected), "{uri}");
+    }
+}
+
+#[tokio::test]
+async fn original_path_depends_on_order() {
+    assert_eq!(
+        sanitize_then_normalize("//a//b//").await,
+        r#"/a/b Some("//a//b//")"#
+    );
+
+    // The trailing slashes were already trimmed when the path was recorded
+    assert_eq!(
+        normalize_then_sanitize("//a//b//").await,
+        r#"/a/b Some("/a//b")"#
+    );
+}
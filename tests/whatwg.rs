@@ -0,0 +1,96 @@
+//! Conformance of `PathStrategy::Whatwg` with the path normalization of the WHATWG URL standard.
+//!
+//! The cases are the `pathname` expectations for `http` URLs vendored from the web-platform-tests
+//! `urltestdata.json` into `tests/data`, limited to those a request path can hold.
+
+use std::convert::Infallible;
+
+use http::{uri::PathAndQuery, Request, Response};
+use serde_json::Value;
+use tower::{service_fn, Layer, ServiceExt};
+use tower_sanitize_path::{PathStrategy, SanitizeConfig, SanitizePathLayer};
+
+const URLTESTDATA: &str = include_str!("data/urltestdata.json");
+
+/// Cases of the path state `urltestdata.json` only covers with inputs a request path cannot
+/// hold, like a `\` right after the host or characters left for the parser to encode.
+const PATH_STATE_CASES: &[(&str, &str)] = &[
+    ("/foo\\bar", "/foo/bar"),
+    ("/foo/..\\..\\bar", "/bar"),
+    ("/\"quoted\"", "/%22quoted%22"),
+    ("/a{b}", "/a%7Bb%7D"),
+    ("/", "/"),
+];
+
+/// The request path of each `http` entry in `urltestdata.json` along with its `pathname`, or
+/// `None` for entries whose path cannot be received in a request.
+fn urltestdata() -> Vec<(String, Option<String>)> {
+    let entries: Vec<Value> = serde_json::from_str(URLTESTDATA).unwrap();
+
+    entries
+        .iter()
+        // Strings are comments
+        .filter_map(Value::as_object)
+        .filter(|entry| entry["protocol"] == "http:")
+        .map(|entry| {
+            let input = entry["input"].as_str().unwrap();
+            let path = match input.strip_prefix("http://") {
+                Some(rest) => rest.find(['/', '\\']).map_or("", |start| &rest[start..]),
+                None => input,
+            };
+            let pathname = entry["pathname"].as_str().unwrap().to_owned();
+
+            let representable = path.starts_with('/') && path.parse::<PathAndQuery>().is_ok();
+            (path.to_owned(), representable.then_some(pathname))
+        })
+        .collect()
+}
+
+async fn echo(req: Request<()>) -> Result<Response<String>, Infallible> {
+    Ok(Response::new(req.uri().path().to_string()))
+}
+
+async fn sanitize(uri: &str) -> String {
+    let config = SanitizeConfig::new().path_strategy(PathStrategy::Whatwg);
+    let service = SanitizePathLayer::with_config(config).layer(service_fn(echo));
+    let request = Request::builder().uri(uri).body(()).unwrap();
+
+    service.oneshot(request).await.unwrap().into_body()
+}
+
+#[tokio::test]
+async fn urltestdata_paths() {
+    let cases = urltestdata();
+    let mut ran = 0;
+
+    for (input, expected) in &cases {
+        if let Some(expected) = expected {
+            assert_eq!(sanitize(input).await, *expected, "{input}");
+            ran += 1;
+        }
+    }
+
+    // Tabs, spaces, non-ASCII and a `\` after the host never reach a service
+    assert_eq!((ran, cases.len()), (29, 38));
+}
+
+#[tokio::test]
+async fn path_state() {
+    for &(input, expected) in PATH_STATE_CASES {
+        assert_eq!(sanitize(input).await, expected, "{input}");
+    }
+}
+
+#[tokio::test]
+async fn idempotent() {
+    let expected = urltestdata()
+        .into_iter()
+        .filter_map(|(_, expected)| expected);
+    let path_state = PATH_STATE_CASES
+        .iter()
+        .map(|&(_, expected)| expected.to_owned());
+
+    for expected in expected.chain(path_state) {
+        assert_eq!(sanitize(&expected).await, expected, "{expected}");
+    }
+}
@@ -0,0 +1,49 @@
+//! Sanitizing without fully decoding paths, the only modes available when building without the
+//! `percent-decode` feature:
+//!
+//! ```sh
+//! cargo test --no-default-features --test without_decoding
+//! ```
+
+use std::convert::Infallible;
+
+use http::{Request, Response};
+use tower::{service_fn, Layer, ServiceExt};
+use tower_sanitize_path::{DecodeMode, SanitizeConfig, SanitizePathLayer};
+
+async fn sanitize(config: SanitizeConfig, uri: &str) -> String {
+    let service =
+        SanitizePathLayer::with_config(config).layer(service_fn(|req: Request<()>| async move {
+            Ok::<_, Infallible>(Response::new(req.uri().to_string()))
+        }));
+    let request = Request::builder().uri(uri).body(()).unwrap();
+
+    service.oneshot(request).await.unwrap().into_body()
+}
+
+#[tokio::test]
+async fn literal() {
+    let config = || SanitizeConfig::new().decode_mode(DecodeMode::Literal);
+
+    assert_eq!(sanitize(config(), "/a/../b//c/./d").await, "/b/c/d");
+    assert_eq!(sanitize(config(), "/%2e%2e/%41").await, "/%2e%2e/%41");
+}
+
+#[tokio::test]
+async fn traversal_only() {
+    let config = || SanitizeConfig::new().decode_mode(DecodeMode::TraversalOnly);
+
+    assert_eq!(sanitize(config(), "/a/%2e%2e/b").await, "/b");
+    assert_eq!(sanitize(config(), "/%41/b/%2E%2E%2fc").await, "/%41/c");
+}
+
+#[cfg(not(feature = "percent-decode"))]
+#[tokio::test]
+async fn defaults_to_traversal_only() {
+    assert_eq!(
+        SanitizeConfig::new(),
+        SanitizeConfig::new().decode_mode(DecodeMode::TraversalOnly)
+    );
+    assert_eq!(sanitize(SanitizeConfig::new(), "/%41/%2e%2e/b").await, "/b");
+    assert_eq!(sanitize(SanitizeConfig::new(), "/%41/b").await, "/%41/b");
+}